@@ -55,6 +55,18 @@ describe('checkSwarmProtocol', () => {
         `guard event type ${Events.Opening.type} appears in transitions from multiple states`,
         `guard event type ${Events.Closing.type} appears in transitions from multiple states`,
       ],
+      diagnostics: [
+        {
+          code: 'GuardNotInvariant',
+          message: `guard event type ${Events.Opening.type} appears in transitions from multiple states`,
+          eventTypes: [Events.Opening.type],
+        },
+        {
+          code: 'GuardNotInvariant',
+          message: `guard event type ${Events.Closing.type} appears in transitions from multiple states`,
+          eventTypes: [Events.Closing.type],
+        },
+      ],
     })
   })
 })
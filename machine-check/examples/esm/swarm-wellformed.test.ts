@@ -213,6 +213,38 @@ export namespace WellFormed {
   }
 }
 
+const notWellFormedErrors = {
+  type: 'ERROR',
+  errors: [
+    `missing transition ${Events.Opening.type}? in state Closed (from reference state Closed)`,
+    `extraneous transition ${Events.OpeningStatus.type}? in state Closed`,
+  ],
+  diagnostics: [
+    {
+      code: 'MissingTransition',
+      message: `missing transition ${Events.Opening.type}? in state Closed (from reference state Closed)`,
+      state: 'Closed',
+      transition: {
+        label: { tag: 'Input', eventType: Events.Opening.type },
+        source: 'Closed',
+        target: 'Opening',
+      },
+      eventTypes: [Events.Opening.type],
+    },
+    {
+      code: 'ExtraneousTransition',
+      message: `extraneous transition ${Events.OpeningStatus.type}? in state Closed`,
+      state: 'Closed',
+      transition: {
+        label: { tag: 'Input', eventType: Events.OpeningStatus.type },
+        source: 'Closed',
+        target: 'Opening',
+      },
+      eventTypes: [Events.OpeningStatus.type],
+    },
+  ],
+}
+
 describe('checkProjection', () => {
   describe('not wellformed', () => {
     it('should match Control', () => {
@@ -223,13 +255,7 @@ describe('checkProjection', () => {
           'Control',
           NotWellFormed.Control.machine.createJSONForAnalysis(NotWellFormed.Control.Closed),
         ),
-      ).toEqual({
-        type: 'ERROR',
-        errors: [
-          `missing transition ${Events.Opening.type}? in state Closed (from reference state Closed)`,
-          `extraneous transition ${Events.OpeningStatus.type}? in state Closed`,
-        ],
-      })
+      ).toEqual(notWellFormedErrors)
     })
 
     it('should match Door', () => {
@@ -240,13 +266,7 @@ describe('checkProjection', () => {
           'Door',
           NotWellFormed.Door.machine.createJSONForAnalysis(NotWellFormed.Door.Closed),
         ),
-      ).toEqual({
-        type: 'ERROR',
-        errors: [
-          `missing transition ${Events.Opening.type}? in state Closed (from reference state Closed)`,
-          `extraneous transition ${Events.OpeningStatus.type}? in state Closed`,
-        ],
-      })
+      ).toEqual(notWellFormedErrors)
     })
   })
 
@@ -261,6 +281,7 @@ describe('checkProjection', () => {
         ),
       ).toEqual({
         type: 'OK',
+        data: { Closed: ['Closed'], Closing: ['Closing'], Open: ['Open'], Opening: ['Opening'] },
       })
     })
 
@@ -274,6 +295,7 @@ describe('checkProjection', () => {
         ),
       ).toEqual({
         type: 'OK',
+        data: { Closed: ['Closed'], Closing: ['Closing'], Open: ['Open'], Opening: ['Opening'] },
       })
     })
   })
@@ -0,0 +1,375 @@
+//! Comparing two revisions of a swarm protocol during a gradual rollout
+//!
+//! While a new protocol revision is rolled out, machines of both versions work on the same event
+//! log. This module reports how the transitions changed and, for each role, whether its old
+//! machine can still follow the logs produced by the new version and whether the role can be
+//! upgraded while all other roles still run the old version.
+
+use crate::{
+    machine::{self, state_name},
+    minimise,
+    types::{Diagnostic, ErrorCode, EventType, MachineLabel, Role, State, SwarmLabel, Transition},
+    Graph, NodeId, Subscriptions, SwarmProtocol,
+};
+use petgraph::{visit::EdgeRef, Direction::Outgoing};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Compatibility {
+    /// transitions only present in the new revision
+    pub added: Vec<Transition<SwarmLabel>>,
+    /// transitions only present in the old revision
+    pub removed: Vec<Transition<SwarmLabel>>,
+    /// transitions between the same states whose label changed
+    pub relabelled: Vec<Relabelled>,
+    pub roles: BTreeMap<Role, RoleCompatibility>,
+    /// reasons why roles cannot read the new logs or cannot be upgraded independently
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Relabelled {
+    pub source: State,
+    pub target: State,
+    pub old: SwarmLabel,
+    pub new: SwarmLabel,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RoleCompatibility {
+    /// the projection of the role is the same in both revisions
+    pub unchanged: bool,
+    /// the old machine for this role can process all logs produced by the new revision
+    pub reads_new_logs: bool,
+    /// the role can be upgraded while all other roles still run the old revision
+    pub independent: bool,
+}
+
+/// Compare the old and new revision of a swarm protocol, see the module documentation.
+///
+/// Events a machine does not subscribe to are invisible to it, so a machine is only considered
+/// unable to process a log if it receives an event for which its current state has no input
+/// transition. Machines start in the initial state of the same name as the swarm protocol they
+/// run alongside.
+pub fn check(
+    old: (&Graph, &[NodeId], &Subscriptions),
+    new: (&Graph, &[NodeId], &Subscriptions),
+) -> Compatibility {
+    let _span = tracing::debug_span!("check compatibility").entered();
+    let (added, removed, relabelled) = diff(
+        &crate::swarm::to_json(old.0, old.1),
+        &crate::swarm::to_json(new.0, new.1),
+    );
+
+    let roles = [old, new]
+        .iter()
+        .flat_map(|(g, _, s)| s.keys().chain(g.edge_weights().map(|l| &l.role)))
+        .cloned()
+        .collect::<BTreeSet<_>>();
+    let mut diagnostics = Vec::new();
+    let roles = roles
+        .into_iter()
+        .map(|role| {
+            let (old_proj, old_initials) = machine::project(old.0, old.1, old.2, role.clone());
+            let (new_proj, new_initials) = machine::project(new.0, new.1, new.2, role.clone());
+            let unchanged = {
+                let (l, li, _) = minimise::machine(&old_proj, &old_initials, &[]);
+                let (r, ri, _) = minimise::machine(&new_proj, &new_initials, &[]);
+                machine::equivalent(&l, &li, &r, &ri).is_empty()
+            };
+            let mut follow = Follow {
+                role: &role,
+                diagnostics: Vec::new(),
+            };
+            follow.run(
+                (new.0, new.1),
+                (&old_proj, &old_initials),
+                old.2,
+                "old machine",
+                false,
+            );
+            let reads_new_logs = follow.diagnostics.is_empty();
+            diagnostics.append(&mut follow.diagnostics);
+            follow.run(
+                (old.0, old.1),
+                (&new_proj, &new_initials),
+                new.2,
+                "upgraded machine",
+                true,
+            );
+            let independent = follow.diagnostics.is_empty();
+            diagnostics.append(&mut follow.diagnostics);
+            let compatibility = RoleCompatibility {
+                unchanged,
+                reads_new_logs,
+                independent,
+            };
+            (role, compatibility)
+        })
+        .collect();
+
+    Compatibility {
+        added,
+        removed,
+        relabelled,
+        roles,
+        diagnostics,
+    }
+}
+
+/// Match transitions by their source and target states.
+fn diff(
+    old: &SwarmProtocol,
+    new: &SwarmProtocol,
+) -> (
+    Vec<Transition<SwarmLabel>>,
+    Vec<Transition<SwarmLabel>>,
+    Vec<Relabelled>,
+) {
+    let mut removed = old
+        .transitions
+        .iter()
+        .filter(|t| !new.transitions.contains(t))
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut added = Vec::new();
+    let mut relabelled = Vec::new();
+    for t in new
+        .transitions
+        .iter()
+        .filter(|t| !old.transitions.contains(t))
+    {
+        match removed
+            .iter()
+            .position(|r| r.source == t.source && r.target == t.target)
+        {
+            Some(idx) => {
+                let r = removed.remove(idx);
+                relabelled.push(Relabelled {
+                    source: r.source,
+                    target: r.target,
+                    old: r.label,
+                    new: t.label.clone(),
+                });
+            }
+            None => added.push(t.clone()),
+        }
+    }
+    (added, removed, relabelled)
+}
+
+/// Runs a machine alongside a swarm protocol of the other revision.
+struct Follow<'a> {
+    role: &'a Role,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Follow<'_> {
+    /// Explore all pairs of swarm and machine states reachable by the swarm protocol’s logs.
+    ///
+    /// With `commands`, the machine decides which transitions its role takes: those it does not
+    /// offer are skipped and those the swarm protocol does not allow are reported.
+    fn run(
+        &mut self,
+        (swarm, initials): (&Graph, &[NodeId]),
+        (proj, proj_initials): (&machine::Graph, &[NodeId]),
+        subs: &Subscriptions,
+        what: &str,
+        commands: bool,
+    ) {
+        let role = self.role;
+        let other = if commands { "old" } else { "new" };
+        let empty = BTreeSet::new();
+        let sub = subs.get(role).unwrap_or(&empty);
+        let mut reported = HashSet::new();
+        let mut queue = VecDeque::new();
+        for initial in initials {
+            let state = &swarm[*initial];
+            match proj_initials
+                .iter()
+                .find(|m| proj[**m].as_ref() == Some(state))
+            {
+                Some(m) => queue.push_back((*initial, *m)),
+                None => {
+                    let mut d = Diagnostic::new(
+                        ErrorCode::LogNotConsumable,
+                        format!("{what} for role {role} cannot start in initial state {state} of the {other} protocol"),
+                    );
+                    d.state = Some(state.clone());
+                    d.roles.push(role.clone());
+                    self.diagnostics.push(d);
+                }
+            }
+        }
+        let mut visited = queue.iter().copied().collect::<HashSet<_>>();
+        while let Some((s, m)) = queue.pop_front() {
+            let m_name = State::new(&state_name(proj, m).to_string());
+            if commands {
+                for edge in proj.edges_directed(m, Outgoing) {
+                    let MachineLabel::Execute { cmd, log_type } = edge.weight() else {
+                        continue;
+                    };
+                    let allowed = swarm.edges_directed(s, Outgoing).any(|e| {
+                        let l = e.weight();
+                        l.role == *role && l.cmd == *cmd && l.log_type == *log_type
+                    });
+                    if !allowed && reported.insert((m_name.clone(), edge.weight().to_string())) {
+                        let mut d = Diagnostic::new(
+                            ErrorCode::UnexpectedCommand,
+                            format!(
+                                "{what} for role {role} may execute {} in state {m_name}, which the old protocol does not allow in state {}",
+                                edge.weight(),
+                                swarm[s]
+                            ),
+                        );
+                        d.state = Some(m_name.clone());
+                        d.roles.push(role.clone());
+                        d.event_types = log_type.clone();
+                        self.diagnostics.push(d);
+                    }
+                }
+            }
+            for edge in swarm.edges_directed(s, Outgoing) {
+                let label = edge.weight();
+                if commands && label.role == *role {
+                    let offered = proj.edges_directed(m, Outgoing).any(|e| {
+                        matches!(e.weight(), MachineLabel::Execute { cmd, log_type }
+                            if *cmd == label.cmd && *log_type == label.log_type)
+                    });
+                    if !offered {
+                        continue;
+                    }
+                }
+                let log = label.log_type.iter().filter(|ev| sub.contains(*ev));
+                match consume(proj, m, log) {
+                    Ok(next) => {
+                        if visited.insert((edge.target(), next)) {
+                            queue.push_back((edge.target(), next));
+                        }
+                    }
+                    Err((event_type, stuck)) => {
+                        let stuck = State::new(&state_name(proj, stuck).to_string());
+                        if !reported.insert((stuck.clone(), event_type.to_string())) {
+                            continue;
+                        }
+                        let mut d = Diagnostic::new(
+                            ErrorCode::LogNotConsumable,
+                            format!(
+                                "{what} for role {role} cannot process event type {event_type} in state {stuck}, emitted by transition ({})--[{label}]-->({}) of the {other} protocol",
+                                swarm[s],
+                                swarm[edge.target()]
+                            ),
+                        );
+                        d.state = Some(stuck);
+                        d.roles.push(role.clone());
+                        d.event_types.push(event_type.clone());
+                        self.diagnostics.push(d);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Feed the events into the machine, returning the state reached or the first event that
+/// could not be processed.
+fn consume<'a>(
+    proj: &machine::Graph,
+    mut state: NodeId,
+    log: impl Iterator<Item = &'a EventType>,
+) -> Result<NodeId, (&'a EventType, NodeId)> {
+    for event_type in log {
+        state = proj
+            .edges_directed(state, Outgoing)
+            .find(
+                |e| matches!(e.weight(), MachineLabel::Input { event_type: t } if t == event_type),
+            )
+            .map(|e| e.target())
+            .ok_or((event_type, state))?;
+    }
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::text::parse_swarm;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn door() {
+        let old = parse_swarm(
+            "initial Closed\n\
+             (Closed) --[open@Control<opening>]--> (Opening)\n\
+             (Opening) --[finish@Door<opened>]--> (Open)\n\
+             (Open) --[close@Control<closed>]--> (Closed)\n",
+        )
+        .unwrap();
+        let new = parse_swarm(
+            "initial Closed\n\
+             (Closed) --[open@Control<opening>]--> (Opening)\n\
+             (Opening) --[finish@Door<opened>]--> (Open)\n\
+             (Open) --[close@Control<closing,closed>]--> (Closed)\n\
+             (Closed) --[lock@Control<locked>]--> (Locked)\n",
+        )
+        .unwrap();
+        let subs_old = serde_json::from_str(
+            r#"{
+                "Control": ["opening", "opened", "closed"],
+                "Door": ["opening", "opened", "closed"],
+                "Logger": ["opening", "opened", "closed"]
+            }"#,
+        )
+        .unwrap();
+        let subs_new = serde_json::from_str(
+            r#"{
+                "Control": ["opening", "opened", "closing", "closed", "locked"],
+                "Door": ["opening", "opened", "closing", "closed"],
+                "Logger": ["opening", "opened", "closed"]
+            }"#,
+        )
+        .unwrap();
+        let result = crate::check_compatibility(&old, &new, &subs_old, &subs_new).unwrap();
+        assert_eq!(
+            result
+                .added
+                .iter()
+                .map(|t| format!("({})--[{}]-->({})", t.source, t.label, t.target))
+                .collect::<Vec<_>>(),
+            vec!["(Closed)--[lock@Control<locked>]-->(Locked)"]
+        );
+        assert_eq!(result.removed, vec![]);
+        assert_eq!(
+            result
+                .relabelled
+                .iter()
+                .map(|r| format!("{} => {}", r.old, r.new))
+                .collect::<Vec<_>>(),
+            vec!["close@Control<closed> => close@Control<closing,closed>"]
+        );
+        assert_eq!(
+            serde_json::to_value(&result.roles).unwrap(),
+            serde_json::json!({
+                "Control": { "unchanged": false, "readsNewLogs": true, "independent": false },
+                "Door": { "unchanged": false, "readsNewLogs": true, "independent": false },
+                "Logger": { "unchanged": true, "readsNewLogs": true, "independent": true },
+            })
+        );
+        assert_eq!(
+            result
+                .diagnostics
+                .iter()
+                .map(|d| d.message.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "upgraded machine for role Control may execute lock/locked in state Closed, which the old protocol does not allow in state Closed",
+                "upgraded machine for role Control may execute close/closing,closed in state Open, which the old protocol does not allow in state Open",
+                "upgraded machine for role Door cannot process event type closed in state Open, emitted by transition (Open)--[close@Control<closed>]-->(Closed) of the old protocol",
+            ]
+        );
+    }
+}
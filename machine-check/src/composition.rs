@@ -0,0 +1,648 @@
+//! Composition of swarm protocols that share roles and event types
+
+use crate::{
+    dfs, swarm,
+    types::{Command, Diagnostic, ErrorCode, EventType, Role, State, SwarmLabel, Transition},
+    NodeId, Subscriptions, SwarmProtocol,
+};
+use itertools::Itertools;
+use petgraph::{
+    visit::{EdgeRef, Walker},
+    Direction::Outgoing,
+};
+use std::collections::{BTreeSet, HashSet, VecDeque};
+
+/// Problems found by [`check_interfaces`]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Error {
+    /// a problem in the component protocol with the given index, possibly caused by the roles
+    /// of other components; node and edge IDs refer to the graph of that component
+    Component(usize, swarm::Error),
+    /// the event type is emitted by different transitions in the two protocols
+    EventTypeMismatch(EventType, usize, usize),
+    /// the command is used with different transitions in the two protocols
+    CommandMismatch(Role, Command, usize, usize),
+    /// the two protocols declare different payload schemas for the event type
+    SchemaMismatch(EventType, usize, usize),
+}
+
+impl Error {
+    pub fn to_string(&self, graphs: &[crate::Graph]) -> String {
+        match self {
+            Error::Component(idx, err) => format!("protocol {idx}: {}", err.to_string(&graphs[*idx])),
+            Error::EventTypeMismatch(ev, left, right) => format!(
+                "event type {ev} is emitted by different transitions in protocols {left} and {right}"
+            ),
+            Error::CommandMismatch(role, cmd, left, right) => format!(
+                "command {cmd}@{role} is used with different transitions in protocols {left} and {right}"
+            ),
+            Error::SchemaMismatch(ev, left, right) => format!(
+                "event type {ev} has different payload schemas in protocols {left} and {right}"
+            ),
+        }
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Error::Component(_, err) => err.code(),
+            Error::EventTypeMismatch(..) | Error::CommandMismatch(..) => {
+                ErrorCode::InterfaceMismatch
+            }
+            Error::SchemaMismatch(..) => ErrorCode::SchemaMismatch,
+        }
+    }
+
+    pub fn to_diagnostic(&self, graphs: &[crate::Graph]) -> Diagnostic {
+        let mut d = match self {
+            Error::Component(idx, err) => err.to_diagnostic(&graphs[*idx]),
+            _ => Diagnostic::new(self.code(), String::new()),
+        };
+        d.message = self.to_string(graphs);
+        match self {
+            Error::Component(..) => {}
+            Error::EventTypeMismatch(ev, ..) | Error::SchemaMismatch(ev, ..) => {
+                d.event_types.push(ev.clone())
+            }
+            Error::CommandMismatch(role, ..) => d.roles.push(role.clone()),
+        }
+        d
+    }
+
+    pub fn diagnostic(graphs: &[crate::Graph]) -> impl Fn(Error) -> Diagnostic + '_ {
+        |err| err.to_diagnostic(graphs)
+    }
+}
+
+/// Compose the given swarm protocols into one protocol that runs them side by side.
+///
+/// Transitions are interleaved, except for those that emit event types also used by the other
+/// protocols: these must be taken jointly with a transition with the same label in each protocol
+/// that knows any of these event types. States of the composition are named by joining the
+/// component states with `|`, e.g. `S1|T0`, and every combination of initial states is an initial
+/// state of the composition, likewise for final states. Returns `None` if no protocols are given.
+pub fn compose(protocols: &[SwarmProtocol]) -> Option<SwarmProtocol> {
+    let (first, rest) = protocols.split_first()?;
+    Some(
+        rest.iter()
+            .fold(first.clone(), |acc, proto| compose_pair(&acc, proto)),
+    )
+}
+
+/// Merge the subscriptions for the component protocols by taking the union for each role.
+pub fn combine_subscriptions(subs: &[Subscriptions]) -> Subscriptions {
+    let mut combined = Subscriptions::new();
+    for s in subs {
+        for (role, events) in s {
+            combined
+                .entry(role.clone())
+                .or_default()
+                .extend(events.iter().cloned());
+        }
+    }
+    combined
+}
+
+/// Check that the composition of the given protocols is well-formed under the combined
+/// subscriptions, see [`swarm::check`].
+pub fn check(
+    protocols: &[SwarmProtocol],
+    subs: &[Subscriptions],
+) -> Option<(crate::Graph, Option<Vec<NodeId>>, Vec<swarm::Error>)> {
+    let composed = compose(protocols)?;
+    Some(swarm::check(composed, &combine_subscriptions(subs)))
+}
+
+/// Check that all protocols declaring a payload schema for the same event type agree on it.
+pub fn schema_mismatches(protocols: &[SwarmProtocol]) -> Vec<Error> {
+    let mut errors = Vec::new();
+    for (i, left) in protocols.iter().enumerate() {
+        for (j, right) in protocols.iter().enumerate().skip(i + 1) {
+            for (ev, schema) in &left.schemas {
+                if right.schemas.get(ev).is_some_and(|s| s != schema) {
+                    errors.push(Error::SchemaMismatch(ev.clone(), i, j));
+                }
+            }
+        }
+    }
+    errors
+}
+
+/// Check that the composition of the given protocols is well-formed under the combined
+/// subscriptions without computing the composition.
+///
+/// Each component is checked with [`swarm::check`], where the roles that are active or involved
+/// after a transition also include those of the states other components may be in at that time.
+/// These states are approximated from the shared event types that must have been emitted before
+/// reaching a state in one component and that may have been emitted in the other. In addition,
+/// shared event types and commands must be used with the same transitions in all protocols.
+///
+/// This check is conservative: if it passes then so does [`check`], but it may report problems
+/// for compositions that are well-formed. The returned graphs belong to the components.
+pub fn check_interfaces(
+    protocols: &[SwarmProtocol],
+    subs: &[Subscriptions],
+) -> (Vec<crate::Graph>, Vec<Error>) {
+    let subs = combine_subscriptions(subs);
+    let mut errors = BTreeSet::new();
+    let components = protocols
+        .iter()
+        .enumerate()
+        .map(|(idx, proto)| {
+            let (graph, initials, errs) = swarm::check_annotated(proto.clone(), &subs);
+            errors.extend(errs.into_iter().map(|e| Error::Component(idx, e)));
+            Component::new(graph, initials.unwrap_or_default(), event_types(proto))
+        })
+        .collect::<Vec<_>>();
+
+    for (i, left) in components.iter().enumerate() {
+        for (j, right) in components.iter().enumerate().skip(i + 1) {
+            for ev in left.events.intersection(&right.events) {
+                if left.labels(|l| l.log_type.contains(ev))
+                    != right.labels(|l| l.log_type.contains(ev))
+                {
+                    errors.insert(Error::EventTypeMismatch(ev.clone(), i, j));
+                }
+            }
+            let commands = |c: &Component| {
+                c.labels(|_| true)
+                    .into_iter()
+                    .map(|l| (l.role, l.cmd))
+                    .collect::<BTreeSet<_>>()
+            };
+            for (role, cmd) in commands(left).intersection(&commands(right)) {
+                let used = |l: &SwarmLabel| l.role == *role && l.cmd == *cmd;
+                if left.labels(used) != right.labels(used) {
+                    errors.insert(Error::CommandMismatch(role.clone(), cmd.clone(), i, j));
+                }
+            }
+        }
+    }
+    errors.extend(schema_mismatches(protocols));
+
+    for (i, comp) in components.iter().enumerate() {
+        for edge in comp.edges() {
+            let (source, target) = (edge.source(), edge.target());
+            let label = edge.weight();
+            let mut active = comp.graph[target].active.clone();
+            let mut roles = comp.graph[target].roles.clone();
+            for (j, other) in components.iter().enumerate().filter(|(j, _)| *j != i) {
+                let states = if label.log_type.iter().any(|ev| other.events.contains(ev)) {
+                    // shared transitions are taken jointly
+                    other
+                        .edges()
+                        .filter(|e| e.weight() == label)
+                        .map(|e| e.target())
+                        .collect::<Vec<_>>()
+                } else {
+                    other
+                        .nodes()
+                        .filter(|n| comp.concurrent(source, other, *n))
+                        .filter(|n| comp.concurrent(target, other, *n))
+                        .collect()
+                };
+                for state in states {
+                    tracing::debug!(component = j, state = %other.graph[state].name, "concurrent");
+                    active.extend(other.graph[state].active.iter().cloned());
+                    roles.extend(other.graph[state].roles.iter().cloned());
+                }
+            }
+            errors.extend(
+                swarm::subscription_errors(edge.id(), &label.log_type, &active, &roles, &subs)
+                    .into_iter()
+                    .map(|e| Error::Component(i, e)),
+            );
+        }
+    }
+
+    // confusion-freeness: a guard must not be emitted from several states of the composition
+    let guards = components
+        .iter()
+        .flat_map(|c| c.edges().map(|e| e.weight().log_type[0].clone()))
+        .collect::<BTreeSet<_>>();
+    for guard in guards {
+        let Some(i) = components.iter().position(|c| c.events.contains(&guard)) else {
+            continue;
+        };
+        let comp = &components[i];
+        let sources = comp
+            .edges()
+            .filter(|e| e.weight().log_type.contains(&guard))
+            .map(|e| e.source())
+            .collect::<BTreeSet<_>>();
+        let variant = components
+            .iter()
+            .filter(|other| !other.events.contains(&guard))
+            .any(|other| {
+                let states = other
+                    .nodes()
+                    .filter(|n| sources.iter().any(|s| comp.concurrent(*s, other, *n)))
+                    .count();
+                states > 1
+            });
+        if variant {
+            errors.insert(Error::Component(i, swarm::Error::GuardNotInvariant(guard)));
+        }
+    }
+
+    let graphs = components
+        .iter()
+        .map(|c| swarm::to_swarm(&c.graph))
+        .collect();
+    (graphs, errors.into_iter().collect())
+}
+
+/// A component protocol with the shared event types that may or must precede each state
+struct Component {
+    graph: swarm::Graph,
+    initials: Vec<NodeId>,
+    events: BTreeSet<EventType>,
+    /// event types emitted on some path from an initial state
+    may: Vec<BTreeSet<EventType>>,
+    /// event types emitted on all paths from the initial states, `None` if unreachable
+    must: Vec<Option<BTreeSet<EventType>>>,
+}
+
+impl Component {
+    fn new(graph: swarm::Graph, initials: Vec<NodeId>, events: BTreeSet<EventType>) -> Self {
+        let mut may = vec![BTreeSet::new(); graph.node_count()];
+        let mut must = vec![None; graph.node_count()];
+        for initial in &initials {
+            must[initial.index()] = Some(BTreeSet::new());
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for edge in graph.edge_references() {
+                let (s, t) = (edge.source().index(), edge.target().index());
+                let Some(before) = &must[s] else {
+                    continue;
+                };
+                let log = edge.weight().log_type.iter().cloned();
+                let after = before.iter().cloned().chain(log.clone()).collect();
+                let after = match &must[t] {
+                    _ if initials.iter().any(|i| i.index() == t) => BTreeSet::new(),
+                    Some(m) => m.intersection(&after).cloned().collect(),
+                    None => after,
+                };
+                if must[t].as_ref() != Some(&after) {
+                    must[t] = Some(after);
+                    changed = true;
+                }
+                let after = may[s].iter().cloned().chain(log).collect::<BTreeSet<_>>();
+                if !after.is_subset(&may[t]) {
+                    may[t].extend(after);
+                    changed = true;
+                }
+            }
+        }
+        Self {
+            graph,
+            initials,
+            events,
+            may,
+            must,
+        }
+    }
+
+    /// reachable states
+    fn nodes(&self) -> impl Iterator<Item = NodeId> + '_ {
+        dfs(&self.graph, &self.initials).iter(&self.graph)
+    }
+
+    /// transitions leaving reachable states
+    fn edges(&self) -> impl Iterator<Item = petgraph::graph::EdgeReference<'_, SwarmLabel>> {
+        self.nodes()
+            .flat_map(|n| self.graph.edges_directed(n, Outgoing))
+    }
+
+    fn labels(&self, pred: impl Fn(&SwarmLabel) -> bool) -> BTreeSet<SwarmLabel> {
+        self.edges()
+            .map(|e| e.weight())
+            .filter(|l| pred(l))
+            .cloned()
+            .collect()
+    }
+
+    /// whether the state of this component may coexist with the state of the other one
+    ///
+    /// Shared event types that must have been emitted on one side must have been emitted on
+    /// some path to the state on the other side as well.
+    fn concurrent(&self, node: NodeId, other: &Component, other_node: NodeId) -> bool {
+        let (Some(must), Some(other_must)) =
+            (&self.must[node.index()], &other.must[other_node.index()])
+        else {
+            return false;
+        };
+        must.iter()
+            .filter(|ev| other.events.contains(*ev))
+            .all(|ev| other.may[other_node.index()].contains(ev))
+            && other_must
+                .iter()
+                .filter(|ev| self.events.contains(*ev))
+                .all(|ev| self.may[node.index()].contains(ev))
+    }
+}
+
+fn event_types(proto: &SwarmProtocol) -> BTreeSet<EventType> {
+    proto
+        .transitions
+        .iter()
+        .flat_map(|t| t.label.log_type.iter().cloned())
+        .collect()
+}
+
+fn compose_pair(left: &SwarmProtocol, right: &SwarmProtocol) -> SwarmProtocol {
+    let _span = tracing::debug_span!("compose_pair").entered();
+    let shared = &event_types(left) & &event_types(right);
+    let is_shared = |label: &SwarmLabel| label.log_type.iter().any(|ev| shared.contains(ev));
+    let outgoing = |proto: &'_ SwarmProtocol, state: &State| {
+        proto
+            .transitions
+            .iter()
+            .filter(|t| t.source == *state)
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    let name = |(l, r): &(State, State)| State::new(&format!("{l}|{r}"));
+
+    let initial = left
+        .initial
+        .iter()
+        .cartesian_product(&right.initial)
+        .map(|(l, r)| (l.clone(), r.clone()))
+        .collect::<Vec<_>>();
+    let mut transitions = Vec::new();
+    let mut visited = initial.iter().cloned().collect::<HashSet<_>>();
+    let mut reached = initial.clone();
+    let mut queue = initial.iter().cloned().collect::<VecDeque<_>>();
+    while let Some(state) = queue.pop_front() {
+        let (l, r) = &state;
+        let r_out = outgoing(right, r);
+        let mut targets = Vec::new();
+        for t in outgoing(left, l) {
+            if !is_shared(&t.label) {
+                targets.push((t.label, (t.target, r.clone())));
+                continue;
+            }
+            // shared transitions need a partner with the same label on the other side
+            for u in r_out.iter().filter(|u| u.label == t.label) {
+                tracing::debug!(label = %t.label, "synchronising");
+                targets.push((t.label.clone(), (t.target.clone(), u.target.clone())));
+            }
+        }
+        for u in r_out {
+            if !is_shared(&u.label) {
+                targets.push((u.label, (l.clone(), u.target)));
+            }
+        }
+        for (label, target) in targets {
+            transitions.push(Transition {
+                label,
+                source: name(&state),
+                target: name(&target),
+            });
+            if visited.insert(target.clone()) {
+                reached.push(target.clone());
+                queue.push_back(target);
+            }
+        }
+    }
+    SwarmProtocol {
+        initial: initial.iter().map(name).collect(),
+        transitions,
+        // a composed state is final if all its components are
+        final_states: reached
+            .iter()
+            .filter(|(l, r)| left.final_states.contains(l) && right.final_states.contains(r))
+            .map(name)
+            .collect(),
+        // mismatching schemas are reported by `schema_mismatches`
+        schemas: right
+            .schemas
+            .iter()
+            .chain(&left.schemas)
+            .map(|(ev, schema)| (ev.clone(), schema.clone()))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        text::{parse_swarm, print_swarm},
+        types::{Role, Schema},
+    };
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn transport_warehouse() {
+        let transport = parse_swarm(
+            "initial T0\n\
+             (T0) --[request@Warehouse<needed>]--> (T1)\n\
+             (T1) --[deliver@Robot<delivered>]--> (T2)\n",
+        )
+        .unwrap();
+        let warehouse = parse_swarm(
+            "initial W0\n\
+             (W0) --[deliver@Robot<delivered>]--> (W1)\n\
+             (W1) --[store@Worker<stored>]--> (W2)\n",
+        )
+        .unwrap();
+        let composed = compose(&[transport.clone(), warehouse.clone()]).unwrap();
+        assert_eq!(
+            print_swarm(&composed),
+            "initial T0|W0\n\
+             (T0|W0) --[request@Warehouse<needed>]--> (T1|W0)\n\
+             (T1|W0) --[deliver@Robot<delivered>]--> (T2|W1)\n\
+             (T2|W1) --[store@Worker<stored>]--> (T2|W2)\n"
+        );
+
+        let subs = [
+            serde_json::from_str(
+                r#"{ "Warehouse": ["needed", "delivered"], "Robot": ["needed", "delivered"] }"#,
+            )
+            .unwrap(),
+            serde_json::from_str(
+                r#"{ "Robot": ["delivered", "stored"], "Worker": ["delivered", "stored"] }"#,
+            )
+            .unwrap(),
+        ];
+        // each component is well-formed on its own ...
+        for (proto, subs) in [(&transport, &subs[0]), (&warehouse, &subs[1])] {
+            assert_eq!(swarm::check(proto.clone(), subs).2, vec![]);
+        }
+
+        // ... but in the composition the Worker comes after the request from the other protocol
+        let protocols = [transport, warehouse];
+        let (graph, _, errors) = check(&protocols, &subs).unwrap();
+        assert_eq!(
+            errors
+                .into_iter()
+                .map(swarm::Error::convert(&graph))
+                .collect::<Vec<_>>(),
+            vec![
+                "subsequently involved role Worker does not subscribe to guard in transition (T0|W0)--[request@Warehouse<needed>]-->(T1|W0)",
+            ]
+        );
+
+        let mut subs = subs;
+        subs[1]
+            .get_mut(&Role::new("Worker"))
+            .unwrap()
+            .insert(EventType::new("needed"));
+        assert_eq!(check(&protocols, &subs).unwrap().2, vec![]);
+    }
+
+    #[test]
+    fn interfaces() {
+        let transport = parse_swarm(
+            "initial T0\n\
+             (T0) --[request@Warehouse<needed>]--> (T1)\n\
+             (T1) --[deliver@Robot<delivered>]--> (T2)\n",
+        )
+        .unwrap();
+        let warehouse = parse_swarm(
+            "initial W0\n\
+             (W0) --[deliver@Robot<delivered>]--> (W1)\n\
+             (W1) --[store@Worker<stored>]--> (W2)\n",
+        )
+        .unwrap();
+        let protocols = [transport, warehouse];
+        let mut subs: [Subscriptions; 2] = [
+            serde_json::from_str(
+                r#"{ "Warehouse": ["needed", "delivered"], "Robot": ["needed", "delivered"] }"#,
+            )
+            .unwrap(),
+            serde_json::from_str(
+                r#"{ "Robot": ["delivered", "stored"], "Worker": ["delivered", "stored"] }"#,
+            )
+            .unwrap(),
+        ];
+        let (graphs, errors) = check_interfaces(&protocols, &subs);
+        assert_eq!(
+            errors
+                .iter()
+                .map(|e| e.to_string(&graphs))
+                .collect::<Vec<_>>(),
+            vec![
+                "protocol 0: subsequently involved role Worker does not subscribe to guard in transition (T0)--[request@Warehouse<needed>]-->(T1)",
+            ]
+        );
+
+        subs[1]
+            .get_mut(&Role::new("Worker"))
+            .unwrap()
+            .insert(EventType::new("needed"));
+        assert_eq!(check_interfaces(&protocols, &subs).1, vec![]);
+        assert_eq!(check(&protocols, &subs).unwrap().2, vec![]);
+    }
+
+    #[test]
+    fn interfaces_concurrent() {
+        let left = parse_swarm("initial A0\n(A0) --[a@R1<x>]--> (A1)\n").unwrap();
+        let right = parse_swarm("initial B0\n(B0) --[b@R2<y>]--> (B1)\n").unwrap();
+        let subs: Subscriptions =
+            serde_json::from_str(r#"{ "R1": ["x", "y"], "R2": ["x", "y"] }"#).unwrap();
+        let protocols = [left, right];
+        let subs = [subs];
+
+        // both transitions can happen in either order, so their guards are emitted from two states
+        let (_, _, mut errors) = check(&protocols, &subs).unwrap();
+        errors.sort();
+        assert_eq!(
+            errors,
+            vec![
+                swarm::Error::GuardNotInvariant(EventType::new("x")),
+                swarm::Error::GuardNotInvariant(EventType::new("y")),
+            ]
+        );
+        let (_, errors) = check_interfaces(&protocols, &subs);
+        assert_eq!(
+            errors,
+            vec![
+                Error::Component(0, swarm::Error::GuardNotInvariant(EventType::new("x"))),
+                Error::Component(1, swarm::Error::GuardNotInvariant(EventType::new("y"))),
+            ]
+        );
+    }
+
+    #[test]
+    fn interfaces_mismatch() {
+        let left = parse_swarm("initial A0\n(A0) --[deliver@Robot<delivered>]--> (A1)\n").unwrap();
+        let right = parse_swarm(
+            "initial B0\n\
+             (B0) --[drop@Robot<delivered>]--> (B1)\n\
+             (B1) --[deliver@Robot<stored>]--> (B2)\n",
+        )
+        .unwrap();
+        let subs: Subscriptions =
+            serde_json::from_str(r#"{ "Robot": ["delivered", "stored"] }"#).unwrap();
+        let (graphs, errors) = check_interfaces(&[left, right], &[subs]);
+        assert_eq!(
+            errors
+                .into_iter()
+                .map(Error::diagnostic(&graphs))
+                .map(|d| (d.code, d.message))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    ErrorCode::InterfaceMismatch,
+                    "event type delivered is emitted by different transitions in protocols 0 and 1"
+                        .to_owned()
+                ),
+                (
+                    ErrorCode::InterfaceMismatch,
+                    "command deliver@Robot is used with different transitions in protocols 0 and 1"
+                        .to_owned()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn schema_mismatch() {
+        let mut left =
+            parse_swarm("initial A0\n(A0) --[deliver@Robot<delivered>]--> (A1)\n").unwrap();
+        let mut right =
+            parse_swarm("initial B0\n(B0) --[deliver@Robot<delivered>]--> (B1)\n").unwrap();
+        let delivered = EventType::new("delivered");
+        left.schemas.insert(
+            delivered.clone(),
+            Schema(serde_json::json!({ "type": "string" })),
+        );
+        right.schemas.insert(
+            delivered.clone(),
+            Schema(serde_json::json!({ "type": "object" })),
+        );
+        let subs: Subscriptions = serde_json::from_str(r#"{ "Robot": ["delivered"] }"#).unwrap();
+
+        let (_, errors) =
+            check_interfaces(&[left.clone(), right.clone()], std::slice::from_ref(&subs));
+        assert_eq!(errors, vec![Error::SchemaMismatch(delivered.clone(), 0, 1)]);
+        let diagnostics =
+            crate::check_composition(&[left.clone(), right.clone()], &[subs]).unwrap_err();
+        assert_eq!(
+            diagnostics
+                .into_iter()
+                .map(|d| (d.code, d.message))
+                .collect::<Vec<_>>(),
+            vec![(
+                ErrorCode::SchemaMismatch,
+                "event type delivered has different payload schemas in protocols 0 and 1"
+                    .to_owned()
+            )]
+        );
+        // the first protocol wins in the composition
+        let composed = compose(&[left.clone(), right]).unwrap();
+        assert_eq!(composed.schemas, left.schemas);
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(compose(&[]), None);
+        let diagnostics = crate::check_composition(&[], &[]).unwrap_err();
+        assert_eq!(diagnostics[0].code, ErrorCode::ParseError);
+        assert_eq!(diagnostics[0].message, "no swarm protocols to compose");
+    }
+}
@@ -0,0 +1,485 @@
+//! Bounded exploration of the projected machines under partial event delivery
+//!
+//! All roles run their projections concurrently on top of a shared log. A role may execute any
+//! command enabled in its local state, which adds the emitted events as one block to the log at
+//! any position after the events the role has already seen, i.e. concurrent commands may end up
+//! in either order. Events reach the subscribed roles one by one and in any order, the emitting
+//! role receives its own events immediately. Each role applies the events it has received in log
+//! order, like a machine runner that travels back in time when an event arrives late.
+//!
+//! The swarm protocol only accepts an event if it starts a transition enabled in the current
+//! state or continues the transition in progress, all other events are discarded.
+
+use crate::{
+    machine,
+    types::{Diagnostic, ErrorCode, EventType, MachineLabel, Role, State, SwarmLabel},
+    Graph, NodeId, Subscriptions,
+};
+use petgraph::{visit::EdgeRef, Direction::Outgoing};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExploreOptions {
+    /// number of commands and deliveries after which the exploration stops
+    #[serde(default = "default_max_depth")]
+    pub max_depth: usize,
+    /// number of distinct configurations after which the exploration stops
+    #[serde(default = "default_max_states")]
+    pub max_states: usize,
+}
+
+fn default_max_depth() -> usize {
+    8
+}
+
+fn default_max_states() -> usize {
+    100_000
+}
+
+impl Default for ExploreOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: default_max_depth(),
+            max_states: default_max_states(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Exploration {
+    /// number of distinct configurations visited
+    pub states: usize,
+    /// whether some configurations were not explored due to the limits
+    pub truncated: bool,
+    /// the first problem found, if any
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub diagnostics: Vec<Diagnostic>,
+    /// the shortest sequence of steps leading to the problem
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub witness: Vec<Step>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "tag")]
+pub enum Step {
+    /// the role executes a command, adding its events to the log at the given position
+    Execute {
+        role: Role,
+        label: MachineLabel,
+        position: usize,
+    },
+    /// an event emitted by `from` reaches `role`
+    #[serde(rename_all = "camelCase")]
+    Deliver {
+        role: Role,
+        event_type: EventType,
+        from: Role,
+    },
+}
+
+/// event identified by its block and its index within the block
+type EventId = (usize, usize);
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Config {
+    /// commands in the order of their creation
+    blocks: Vec<SwarmLabel>,
+    /// the log as a sequence of blocks
+    log: Vec<usize>,
+    received: BTreeMap<Role, BTreeSet<EventId>>,
+}
+
+struct Explorer<'a> {
+    swarm: &'a Graph,
+    initial: NodeId,
+    subs: &'a Subscriptions,
+    machines: BTreeMap<Role, (machine::Graph, NodeId)>,
+}
+
+/// Explore all interleavings of commands and deliveries up to the configured depth, checking that
+/// no role rejects an event accepted by the swarm protocol and that every role that has received
+/// all events it subscribes to ends up in the state the accepted events lead its projection to.
+///
+/// The initial states are explored one after the other, each with its own bounds; the exploration
+/// stops at the first violation.
+pub fn explore(
+    swarm: &Graph,
+    initials: &[NodeId],
+    subs: &Subscriptions,
+    options: &ExploreOptions,
+) -> Exploration {
+    let _span = tracing::debug_span!("explore").entered();
+    let roles = subs
+        .keys()
+        .chain(swarm.edge_weights().map(|l| &l.role))
+        .cloned()
+        .collect::<BTreeSet<_>>();
+    let mut result = Exploration {
+        states: 0,
+        truncated: false,
+        diagnostics: Vec::new(),
+        witness: Vec::new(),
+    };
+    for initial in initials {
+        let machines = roles
+            .iter()
+            .map(|r| {
+                let (proj, proj_initials) = machine::project(swarm, &[*initial], subs, r.clone());
+                (r.clone(), (proj, proj_initials[0]))
+            })
+            .collect();
+        let explorer = Explorer {
+            swarm,
+            initial: *initial,
+            subs,
+            machines,
+        };
+        let exploration = explorer.run(&roles, options);
+        result.states += exploration.states;
+        result.truncated |= exploration.truncated;
+        if !exploration.diagnostics.is_empty() {
+            result.diagnostics = exploration.diagnostics;
+            result.witness = exploration.witness;
+            break;
+        }
+    }
+    result
+}
+
+impl Explorer<'_> {
+    fn run(&self, roles: &BTreeSet<Role>, options: &ExploreOptions) -> Exploration {
+        let start = Config {
+            blocks: Vec::new(),
+            log: Vec::new(),
+            received: roles.iter().map(|r| (r.clone(), BTreeSet::new())).collect(),
+        };
+        // all visited configurations with the step that led there from their parent
+        let mut visited = vec![(start.clone(), None::<(usize, Step)>)];
+        let mut index = HashMap::from([(start, 0)]);
+        let mut queue = VecDeque::from([(0, 0)]);
+        let mut truncated = false;
+        while let Some((idx, depth)) = queue.pop_front() {
+            let config = visited[idx].0.clone();
+            if let Some(diagnostic) = self.violation(&config) {
+                let mut witness = Vec::new();
+                let mut current = idx;
+                while let Some((parent, step)) = &visited[current].1 {
+                    witness.push(step.clone());
+                    current = *parent;
+                }
+                witness.reverse();
+                return Exploration {
+                    states: visited.len(),
+                    truncated,
+                    diagnostics: vec![diagnostic],
+                    witness,
+                };
+            }
+            for (step, next) in self.successors(&config) {
+                if index.contains_key(&next) {
+                    continue;
+                }
+                if depth == options.max_depth || visited.len() == options.max_states {
+                    truncated = true;
+                    break;
+                }
+                index.insert(next.clone(), visited.len());
+                queue.push_back((visited.len(), depth + 1));
+                visited.push((next, Some((idx, step))));
+            }
+        }
+        Exploration {
+            states: visited.len(),
+            truncated,
+            diagnostics: Vec::new(),
+            witness: Vec::new(),
+        }
+    }
+
+    fn events<'b>(&self, config: &'b Config) -> impl Iterator<Item = EventId> + 'b {
+        config
+            .log
+            .iter()
+            .flat_map(|b| (0..config.blocks[*b].log_type.len()).map(|i| (*b, i)))
+    }
+
+    fn event_type<'b>(&self, config: &'b Config, (block, idx): EventId) -> &'b EventType {
+        &config.blocks[block].log_type[idx]
+    }
+
+    /// the events accepted by the swarm protocol
+    fn accepted(&self, config: &Config) -> BTreeSet<EventId> {
+        let mut state = self.initial;
+        let mut pending = None::<(EventId, NodeId)>;
+        let mut accepted = BTreeSet::new();
+        for (block, idx) in self.events(config) {
+            let label = &config.blocks[block];
+            let target = match pending {
+                Some((expected, target)) if expected == (block, idx) => Some(target),
+                Some(_) => None,
+                None if idx == 0 => self
+                    .swarm
+                    .edges_directed(state, Outgoing)
+                    .find(|e| e.weight() == label)
+                    .map(|e| e.target()),
+                None => None,
+            };
+            let Some(target) = target else {
+                continue;
+            };
+            accepted.insert((block, idx));
+            if idx + 1 == label.log_type.len() {
+                state = target;
+                pending = None;
+            } else {
+                pending = Some(((block, idx + 1), target));
+            }
+        }
+        accepted
+    }
+
+    /// apply the given events to the projection of the role, in log order
+    ///
+    /// Returns the state reached and the first event without matching input transition.
+    fn replay(
+        &self,
+        config: &Config,
+        role: &Role,
+        events: impl Iterator<Item = EventId>,
+    ) -> (NodeId, Option<(EventId, NodeId)>) {
+        let (proj, mut state) = &self.machines[role];
+        let mut unexpected = None;
+        for event in events {
+            let event_type = self.event_type(config, event);
+            let next = proj
+                .edges_directed(state, Outgoing)
+                .find(|e| matches!(e.weight(), MachineLabel::Input { event_type: t } if t == event_type))
+                .map(|e| e.target());
+            match next {
+                Some(next) => state = next,
+                None => {
+                    unexpected.get_or_insert((event, state));
+                }
+            }
+        }
+        (state, unexpected)
+    }
+
+    fn subscribed<'b>(
+        &'b self,
+        config: &'b Config,
+        role: &'b Role,
+    ) -> impl Iterator<Item = EventId> + 'b {
+        let types = self.subs.get(role);
+        self.events(config)
+            .filter(move |e| types.is_some_and(|t| t.contains(self.event_type(config, *e))))
+    }
+
+    fn state_name(&self, role: &Role, node: NodeId) -> State {
+        State::new(&machine::state_name(&self.machines[role].0, node).to_string())
+    }
+
+    fn violation(&self, config: &Config) -> Option<Diagnostic> {
+        let accepted = self.accepted(config);
+        for (role, received) in &config.received {
+            // only the events up to the first gap are final, later ones may still be preceded
+            let prefix = self
+                .subscribed(config, role)
+                .take_while(|e| received.contains(e))
+                .collect::<Vec<_>>();
+            let caught_up = prefix.len() == received.len()
+                && self.subscribed(config, role).count() == received.len();
+            let (actual, unexpected) = self.replay(config, role, prefix.iter().copied());
+            if let Some((event, state)) = unexpected.filter(|(e, _)| accepted.contains(e)) {
+                let event_type = self.event_type(config, event);
+                let state = self.state_name(role, state);
+                let from = &config.blocks[event.0].role;
+                let mut d = Diagnostic::new(
+                    ErrorCode::UnexpectedEvent,
+                    format!(
+                        "role {role} cannot process event type {event_type} from role {from} in state {state}"
+                    ),
+                );
+                d.state = Some(state);
+                d.roles.push(role.clone());
+                d.event_types.push(event_type.clone());
+                return Some(d);
+            }
+            if caught_up {
+                let events = prefix.iter().copied().filter(|e| accepted.contains(e));
+                let (expected, _) = self.replay(config, role, events);
+                if expected != actual {
+                    let actual = self.state_name(role, actual);
+                    let expected = self.state_name(role, expected);
+                    let mut d = Diagnostic::new(
+                        ErrorCode::ProjectionDiverged,
+                        format!(
+                            "role {role} is in state {actual} after receiving all its events, but the accepted events lead to state {expected}"
+                        ),
+                    );
+                    d.state = Some(actual);
+                    d.roles.push(role.clone());
+                    return Some(d);
+                }
+            }
+        }
+        None
+    }
+
+    fn successors(&self, config: &Config) -> Vec<(Step, Config)> {
+        let mut result = Vec::new();
+        for (role, received) in &config.received {
+            // deliveries
+            for event in self.subscribed(config, role) {
+                if received.contains(&event) {
+                    continue;
+                }
+                let mut next = config.clone();
+                next.received.get_mut(role).unwrap().insert(event);
+                let step = Step::Deliver {
+                    role: role.clone(),
+                    event_type: self.event_type(config, event).clone(),
+                    from: config.blocks[event.0].role.clone(),
+                };
+                result.push((step, next));
+            }
+
+            // commands, placed anywhere after the last event the role has seen
+            let received_events = self.events(config).filter(|e| received.contains(e));
+            let (state, _) = self.replay(config, role, received_events);
+            let seen = config
+                .log
+                .iter()
+                .rposition(|b| received.iter().any(|(block, _)| block == b))
+                .map_or(0, |p| p + 1);
+            let (proj, _) = &self.machines[role];
+            for edge in proj.edges_directed(state, Outgoing) {
+                let MachineLabel::Execute { cmd, log_type } = edge.weight() else {
+                    continue;
+                };
+                let block = config.blocks.len();
+                for position in seen..=config.log.len() {
+                    let mut next = config.clone();
+                    next.blocks.push(SwarmLabel {
+                        cmd: cmd.clone(),
+                        log_type: log_type.clone(),
+                        role: role.clone(),
+                    });
+                    next.log.insert(position, block);
+                    let own = self.subs.get(role);
+                    next.received.get_mut(role).unwrap().extend(
+                        (0..log_type.len())
+                            .filter(|i| own.is_some_and(|o| o.contains(&log_type[*i])))
+                            .map(|i| (block, i)),
+                    );
+                    let step = Step::Execute {
+                        role: role.clone(),
+                        label: edge.weight().clone(),
+                        position,
+                    };
+                    result.push((step, next));
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{swarm, text::parse_swarm};
+    use pretty_assertions::assert_eq;
+
+    fn setup(proto: &str, subs: &str) -> (Graph, Vec<NodeId>, Subscriptions) {
+        let proto = parse_swarm(proto).unwrap();
+        let subs = serde_json::from_str(subs).unwrap();
+        let (graph, initials, _) = swarm::check(proto, &subs);
+        (graph, initials.unwrap(), subs)
+    }
+
+    const CONFLICT: &str = "initial S0\n\
+        (S0) --[accept@A<accepted>]--> (S1)\n\
+        (S0) --[reject@B<rejected>]--> (S2)\n\
+        (S1) --[ship@C<shipped>]--> (S3)\n";
+
+    fn messages(result: &Exploration) -> Vec<(ErrorCode, &str)> {
+        result
+            .diagnostics
+            .iter()
+            .map(|d| (d.code, d.message.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn conflict() {
+        let (graph, initials, subs) = setup(
+            CONFLICT,
+            r#"{
+                "A": ["accepted", "rejected", "shipped"],
+                "B": ["accepted", "rejected"],
+                "C": ["accepted", "rejected", "shipped"]
+            }"#,
+        );
+        let result = explore(&graph, &initials, &subs, &ExploreOptions::default());
+        assert_eq!(messages(&result), vec![]);
+        assert!(!result.truncated);
+
+        // C does not learn that B’s rejection may have won
+        let (graph, initials, subs) = setup(
+            CONFLICT,
+            r#"{
+                "A": ["accepted", "rejected", "shipped"],
+                "B": ["accepted", "rejected"],
+                "C": ["accepted", "shipped"]
+            }"#,
+        );
+        let result = explore(&graph, &initials, &subs, &ExploreOptions::default());
+        assert_eq!(
+            messages(&result),
+            vec![(
+                ErrorCode::ProjectionDiverged,
+                "role C is in state S1 after receiving all its events, but the accepted events lead to state S0"
+            )]
+        );
+        assert_eq!(
+            serde_json::to_value(&result.witness).unwrap(),
+            serde_json::json!([
+                { "tag": "Execute", "role": "A", "label": { "tag": "Execute", "cmd": "accept", "logType": ["accepted"] }, "position": 0 },
+                { "tag": "Execute", "role": "B", "label": { "tag": "Execute", "cmd": "reject", "logType": ["rejected"] }, "position": 0 },
+                { "tag": "Deliver", "role": "C", "eventType": "accepted", "from": "A" },
+            ])
+        );
+    }
+
+    #[test]
+    fn stuck() {
+        // R2 does not see the loop being closed
+        let (graph, initials, subs) = setup(
+            "initial S0\n\
+             (S0) --[a@R1<A>]--> (S1)\n\
+             (S1) --[b@R1<B>]--> (S0)\n",
+            r#"{ "R1": ["A", "B"], "R2": ["A"] }"#,
+        );
+        let result = explore(&graph, &initials, &subs, &ExploreOptions::default());
+        assert_eq!(
+            messages(&result),
+            vec![(
+                ErrorCode::UnexpectedEvent,
+                "role R2 cannot process event type A from role R1 in state S1"
+            )]
+        );
+        assert_eq!(result.witness.len(), 5);
+
+        let options = ExploreOptions {
+            max_depth: 4,
+            ..ExploreOptions::default()
+        };
+        let result = explore(&graph, &initials, &subs, &options);
+        assert_eq!(messages(&result), vec![]);
+        assert!(result.truncated);
+    }
+}
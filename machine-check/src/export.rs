@@ -0,0 +1,362 @@
+//! Rendering of swarm protocols and machines for visual inspection
+
+use crate::{
+    machine,
+    types::{Diagnostic, DiagnosticLabel, Protocol, State, StateName, SwarmLabel},
+    Machine, NodeId, SwarmProtocol,
+};
+use petgraph::visit::EdgeRef;
+use std::{
+    collections::HashMap,
+    fmt::{self, Write},
+};
+
+const ERROR_COLOUR: &str = "red";
+
+/// Render a swarm protocol graph in Graphviz DOT format.
+///
+/// States and transitions mentioned in the given diagnostics are drawn in red.
+pub fn swarm_to_dot<N: StateName>(
+    graph: &petgraph::Graph<N, SwarmLabel>,
+    initials: &[NodeId],
+    errors: &[Diagnostic],
+) -> String {
+    let nodes = graph
+        .node_indices()
+        .map(|n| (n, graph[n].state_name().to_string()));
+    let edges = graph.edge_references().map(|e| Edge {
+        source: e.source(),
+        target: e.target(),
+        label: e.weight().to_string(),
+        error: errors.iter().any(|d| {
+            d.transition.as_ref().is_some_and(|t| {
+                t.label == DiagnosticLabel::Swarm(e.weight().clone())
+                    && t.source == *graph[e.source()].state_name()
+                    && t.target == *graph[e.target()].state_name()
+            })
+        }),
+    });
+    dot(nodes, edges, initials, errors)
+}
+
+/// Render a machine graph (e.g. a projection) in Graphviz DOT format.
+///
+/// Synthetic intermediate states are named like in error messages, e.g. `S1(+1)`. States and
+/// transitions mentioned in the given diagnostics are drawn in red.
+pub fn machine_to_dot(
+    graph: &machine::Graph,
+    initials: &[NodeId],
+    errors: &[Diagnostic],
+) -> String {
+    let name = |n| machine::state_name(graph, n).to_string();
+    let nodes = graph.node_indices().map(|n| (n, name(n)));
+    let edges = graph.edge_references().map(|e| Edge {
+        source: e.source(),
+        target: e.target(),
+        label: e.weight().to_string(),
+        error: errors.iter().any(|d| {
+            d.transition.as_ref().is_some_and(|t| {
+                t.label == DiagnosticLabel::Machine(e.weight().clone())
+                    && *t.source == *name(e.source())
+                    && *t.target == *name(e.target())
+            })
+        }),
+    });
+    dot(nodes, edges, initials, errors)
+}
+
+struct Edge {
+    source: NodeId,
+    target: NodeId,
+    label: String,
+    error: bool,
+}
+
+fn dot(
+    nodes: impl Iterator<Item = (NodeId, String)>,
+    edges: impl Iterator<Item = Edge>,
+    initials: &[NodeId],
+    errors: &[Diagnostic],
+) -> String {
+    let mut out = String::new();
+    // writing to a String cannot fail
+    write_dot(&mut out, nodes, edges, initials, errors).unwrap();
+    out
+}
+
+fn write_dot(
+    out: &mut String,
+    nodes: impl Iterator<Item = (NodeId, String)>,
+    edges: impl Iterator<Item = Edge>,
+    initials: &[NodeId],
+    errors: &[Diagnostic],
+) -> fmt::Result {
+    writeln!(out, "digraph {{")?;
+    if !initials.is_empty() {
+        writeln!(out, "    start [shape=point];")?;
+    }
+    for initial in initials {
+        writeln!(out, "    start -> n{};", initial.index())?;
+    }
+    for (node, name) in nodes {
+        write!(out, "    n{} [label={}", node.index(), Quoted(&name))?;
+        if errors
+            .iter()
+            .any(|d| d.state.as_ref().is_some_and(|s| **s == *name))
+        {
+            write!(out, ", color={ERROR_COLOUR}, fontcolor={ERROR_COLOUR}")?;
+        }
+        writeln!(out, "];")?;
+    }
+    for edge in edges {
+        write!(
+            out,
+            "    n{} -> n{} [label={}",
+            edge.source.index(),
+            edge.target.index(),
+            Quoted(&edge.label)
+        )?;
+        if edge.error {
+            write!(out, ", color={ERROR_COLOUR}, fontcolor={ERROR_COLOUR}")?;
+        }
+        writeln!(out, "];")?;
+    }
+    writeln!(out, "}}")
+}
+
+/// helper for printing a DOT string literal
+struct Quoted<'a>(&'a str);
+
+impl<'a> fmt::Display for Quoted<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_char('"')?;
+        for c in self.0.chars() {
+            if c == '"' || c == '\\' {
+                f.write_char('\\')?;
+            }
+            f.write_char(c)?;
+        }
+        f.write_char('"')
+    }
+}
+
+/// Render a swarm protocol as Mermaid `stateDiagram-v2`, e.g. for embedding in Markdown.
+pub fn swarm_to_mermaid(proto: &SwarmProtocol) -> String {
+    to_mermaid(proto)
+}
+
+/// Render a machine as Mermaid `stateDiagram-v2`, e.g. for embedding in Markdown.
+pub fn machine_to_mermaid(machine: &Machine) -> String {
+    to_mermaid(machine)
+}
+
+/// Render a swarm protocol as PlantUML state diagram.
+pub fn swarm_to_plantuml(proto: &SwarmProtocol) -> String {
+    to_plantuml(proto)
+}
+
+/// Render a machine as PlantUML state diagram.
+pub fn machine_to_plantuml(machine: &Machine) -> String {
+    to_plantuml(machine)
+}
+
+fn to_mermaid<L: fmt::Display>(proto: &Protocol<L>) -> String {
+    let mut out = String::new();
+    let states = state_ids(proto);
+    let ids = states.iter().cloned().collect::<HashMap<_, _>>();
+    // writing to a String cannot fail
+    let _ = writeln!(out, "stateDiagram-v2");
+    for (state, id) in &states {
+        let _ = writeln!(out, "    state \"{}\" as {id}", mermaid_escape(state));
+    }
+    for initial in &proto.initial {
+        let _ = writeln!(out, "    [*] --> {}", ids[initial]);
+    }
+    for t in &proto.transitions {
+        let label = mermaid_escape(&t.label.to_string());
+        let _ = writeln!(
+            out,
+            "    {} --> {} : {label}",
+            ids[&t.source], ids[&t.target]
+        );
+    }
+    for id in proto.final_states.iter().filter_map(|s| ids.get(s)) {
+        let _ = writeln!(out, "    {id} --> [*]");
+    }
+    out
+}
+
+fn to_plantuml<L: fmt::Display>(proto: &Protocol<L>) -> String {
+    let mut out = String::new();
+    let states = state_ids(proto);
+    let ids = states.iter().cloned().collect::<HashMap<_, _>>();
+    // writing to a String cannot fail
+    let _ = writeln!(out, "@startuml");
+    for (state, id) in &states {
+        let _ = writeln!(out, "state \"{}\" as {id}", state.replace('"', "'"));
+    }
+    for initial in &proto.initial {
+        let _ = writeln!(out, "[*] --> {}", ids[initial]);
+    }
+    for t in &proto.transitions {
+        // `~` is the escape character of PlantUML’s creole markup
+        let label = t.label.to_string().replace('<', "~<").replace('>', "~>");
+        let _ = writeln!(out, "{} --> {} : {label}", ids[&t.source], ids[&t.target]);
+    }
+    for id in proto.final_states.iter().filter_map(|s| ids.get(s)) {
+        let _ = writeln!(out, "{id} --> [*]");
+    }
+    let _ = writeln!(out, "@enduml");
+    out
+}
+
+/// assign diagram identifiers to states in order of appearance, the initial states first
+fn state_ids<L>(proto: &Protocol<L>) -> Vec<(State, String)> {
+    let mut ids = Vec::<(State, String)>::new();
+    let states = proto.initial.iter().chain(
+        proto
+            .transitions
+            .iter()
+            .flat_map(|t| [&t.source, &t.target]),
+    );
+    for state in states {
+        if !ids.iter().any(|(s, _)| s == state) {
+            ids.push((state.clone(), format!("s{}", ids.len())));
+        }
+    }
+    ids
+}
+
+/// Mermaid interprets some characters in labels, these need to be given as entity codes
+fn mermaid_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '#' => out.push_str("#35;"),
+            '<' => out.push_str("#lt;"),
+            '>' => out.push_str("#gt;"),
+            ';' => out.push_str("#59;"),
+            ':' => out.push_str("#58;"),
+            '"' => out.push_str("#quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{types::Role, SwarmProtocol};
+    use pretty_assertions::assert_eq;
+
+    fn proto() -> SwarmProtocol {
+        serde_json::from_str(
+            r#"{
+                "initial": "S0",
+                "transitions": [
+                    { "source": "S0", "target": "S1", "label": { "cmd": "a", "logType": ["A"], "role": "R1" } },
+                    { "source": "S1", "target": "S2", "label": { "cmd": "b", "logType": ["B", "C"], "role": "R2" } },
+                    { "source": "X", "target": "S2", "label": { "cmd": "c", "logType": ["C"], "role": "R1" } }
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn swarm_dot() {
+        let subs = serde_json::from_str(r#"{ "R1": ["A", "B", "C"], "R2": ["B", "C"] }"#).unwrap();
+        assert_eq!(
+            crate::swarm_dot(&proto(), &subs),
+            r#"digraph {
+    start [shape=point];
+    start -> n0;
+    n0 [label="S0"];
+    n1 [label="S1"];
+    n2 [label="S2"];
+    n3 [label="X", color=red, fontcolor=red];
+    n0 -> n1 [label="a@R1<A>", color=red, fontcolor=red];
+    n1 -> n2 [label="b@R2<B,C>"];
+    n3 -> n2 [label="c@R1<C>"];
+}
+"#
+        );
+    }
+
+    #[test]
+    fn projection_dot() {
+        let subs =
+            serde_json::from_str(r#"{ "R1": ["A", "B", "C"], "R2": ["A", "B", "C"] }"#).unwrap();
+        assert_eq!(
+            crate::projection_dot(&proto(), &subs, Role::new("R2")).unwrap(),
+            r#"digraph {
+    start [shape=point];
+    start -> n0;
+    n0 [label="S0"];
+    n1 [label="S1"];
+    n2 [label="S2"];
+    n3 [label="S1(+1)"];
+    n1 -> n1 [label="b/B,C"];
+    n0 -> n1 [label="A?"];
+    n1 -> n3 [label="B?"];
+    n3 -> n2 [label="C?"];
+}
+"#
+        );
+    }
+
+    #[test]
+    fn mermaid() {
+        assert_eq!(
+            super::swarm_to_mermaid(&proto()),
+            r#"stateDiagram-v2
+    state "S0" as s0
+    state "S1" as s1
+    state "S2" as s2
+    state "X" as s3
+    [*] --> s0
+    s0 --> s1 : a@R1#lt;A#gt;
+    s1 --> s2 : b@R2#lt;B,C#gt;
+    s3 --> s2 : c@R1#lt;C#gt;
+"#
+        );
+        let machine = serde_json::from_str(
+            r#"{
+                "initial": "S0",
+                "transitions": [
+                    { "source": "S0", "target": "S0", "label": { "tag": "Execute", "cmd": "a", "logType": ["A"] } },
+                    { "source": "S0", "target": "S1", "label": { "tag": "Input", "eventType": "A" } }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            super::machine_to_mermaid(&machine),
+            r#"stateDiagram-v2
+    state "S0" as s0
+    state "S1" as s1
+    [*] --> s0
+    s0 --> s0 : a/A
+    s0 --> s1 : A?
+"#
+        );
+    }
+
+    #[test]
+    fn plantuml() {
+        assert_eq!(
+            super::swarm_to_plantuml(&proto()),
+            r#"@startuml
+state "S0" as s0
+state "S1" as s1
+state "S2" as s2
+state "X" as s3
+[*] --> s0
+s0 --> s1 : a@R1~<A~>
+s1 --> s2 : b@R2~<B,C~>
+s3 --> s2 : c@R1~<C~>
+@enduml
+"#
+        );
+    }
+}
@@ -1,7 +1,38 @@
-import { check_swarm, check_projection } from '../pkg/machine_check.js'
+import {
+  check_compatibility,
+  check_composition,
+  check_composition_interfaces,
+  check_liveness,
+  check_starvation,
+  check_swarm,
+  check_projection,
+  check_refinement,
+  compose_protocols,
+  explore as explore_swarm,
+  infer_subscriptions,
+  machine_mermaid,
+  machine_plantuml,
+  minimise_machine,
+  minimise_swarm,
+  parse_swarm,
+  print_swarm,
+  project_machine,
+  projection_dot,
+  replay_log,
+  replay_machine,
+  simulate as simulate_swarm,
+  simulate_conflict,
+  suggest_subscription_fixes,
+  validate_payloads,
+  swarm_dot,
+  swarm_mermaid,
+  swarm_plantuml,
+} from '../pkg/machine_check.js'
 
 export type Protocol<Label> = {
-  initial: string
+  initial: string | string[]
+  final?: string | string[]
+  schemas?: Record<string, unknown>
   transitions: { source: string; target: string; label: Label }[]
 }
 export type SwarmLabel = {
@@ -19,7 +50,72 @@ export type MachineType = Protocol<MachineLabel>
 
 export type Subscriptions = Record<string, string[]>
 
-export type Result = { type: 'OK' } | { type: 'ERROR'; errors: string[] }
+export type ErrorCode =
+  | 'ParseError'
+  | 'InitialStateDisconnected'
+  | 'StateUnreachable'
+  | 'LogTypeEmpty'
+  | 'ActiveRoleNotSubscribed'
+  | 'LaterActiveRoleNotSubscribed'
+  | 'LaterInvolvedRoleMoreSubscribed'
+  | 'LaterInvolvedNotGuarded'
+  | 'NonDeterministicGuard'
+  | 'NonDeterministicCommand'
+  | 'GuardNotInvariant'
+  | 'InitialMachineStateDisconnected'
+  | 'CommandNotSelfLoop'
+  | 'NonDeterministicTransition'
+  | 'MissingTransition'
+  | 'ExtraneousTransition'
+  | 'CommandNotOffered'
+  | 'InitialStateMismatch'
+  | 'InterfaceMismatch'
+  | 'UnexpectedEvent'
+  | 'CommandNotEnabled'
+  | 'NoConflict'
+  | 'ConflictUnresolved'
+  | 'ProjectionDiverged'
+  | 'LogNotConsumable'
+  | 'UnexpectedCommand'
+  | 'FinalStateUnknown'
+  | 'NonFinalDeadlock'
+  | 'FinalStateUnreachable'
+  | 'ProjectionNotFinal'
+  | 'RoleStarved'
+  | 'SchemaMismatch'
+  | 'PayloadInvalid'
+  | 'SchemaInvalid'
+
+export type SubscriptionFix = {
+  action: 'add' | 'remove'
+  role: string
+  eventTypes: string[]
+}
+
+export type TraceStep = {
+  label: MachineLabel
+  reference: string
+  specimen: string
+}
+
+export type Diagnostic = {
+  code: ErrorCode
+  message: string
+  state?: string
+  transition?: { source: string; target: string; label: SwarmLabel | MachineLabel }
+  roles?: string[]
+  eventTypes?: string[]
+  fixes?: SubscriptionFix[]
+  trace?: TraceStep[]
+  position?: number
+  expected?: { source: string; target: string; label: SwarmLabel | MachineLabel }[]
+}
+
+export type Result = { type: 'OK' } | { type: 'ERROR'; errors: string[]; diagnostics: Diagnostic[] }
+
+export type DataResult<T> =
+  | { type: 'OK'; data: T }
+  | { type: 'ERROR'; errors: string[]; diagnostics: Diagnostic[] }
 
 export function checkSwarmProtocol(proto: SwarmProtocolType, subscriptions: Subscriptions): Result {
   const p = JSON.stringify(proto)
@@ -28,15 +124,308 @@ export function checkSwarmProtocol(proto: SwarmProtocolType, subscriptions: Subs
   return JSON.parse(result)
 }
 
+export function inferSubscriptions(
+  proto: SwarmProtocolType,
+  subscriptions: Subscriptions = {},
+): DataResult<Subscriptions> {
+  const p = JSON.stringify(proto)
+  const s = JSON.stringify(subscriptions)
+  const result = infer_subscriptions(p, s)
+  return JSON.parse(result)
+}
+
+export function suggestSubscriptionFixes(
+  proto: SwarmProtocolType,
+  subscriptions: Subscriptions,
+): DataResult<Subscriptions> {
+  const p = JSON.stringify(proto)
+  const s = JSON.stringify(subscriptions)
+  const result = suggest_subscription_fixes(p, s)
+  return JSON.parse(result)
+}
+
+export function projectMachine(
+  swarm: SwarmProtocolType,
+  subscriptions: Subscriptions,
+  role: string,
+): DataResult<MachineType> {
+  const sw = JSON.stringify(swarm)
+  const sub = JSON.stringify(subscriptions)
+  const result = project_machine(sw, sub, role)
+  return JSON.parse(result)
+}
+
+export function swarmDot(proto: SwarmProtocolType, subscriptions: Subscriptions): DataResult<string> {
+  const p = JSON.stringify(proto)
+  const s = JSON.stringify(subscriptions)
+  const result = swarm_dot(p, s)
+  return JSON.parse(result)
+}
+
+export function projectionDot(
+  swarm: SwarmProtocolType,
+  subscriptions: Subscriptions,
+  role: string,
+): DataResult<string> {
+  const sw = JSON.stringify(swarm)
+  const sub = JSON.stringify(subscriptions)
+  const result = projection_dot(sw, sub, role)
+  return JSON.parse(result)
+}
+
+export function swarmMermaid(proto: SwarmProtocolType): DataResult<string> {
+  return JSON.parse(swarm_mermaid(JSON.stringify(proto)))
+}
+
+export function machineMermaid(machine: MachineType): DataResult<string> {
+  return JSON.parse(machine_mermaid(JSON.stringify(machine)))
+}
+
+export function swarmPlantUml(proto: SwarmProtocolType): DataResult<string> {
+  return JSON.parse(swarm_plantuml(JSON.stringify(proto)))
+}
+
+export function machinePlantUml(machine: MachineType): DataResult<string> {
+  return JSON.parse(machine_plantuml(JSON.stringify(machine)))
+}
+
+export function parseSwarmProtocol(text: string): DataResult<SwarmProtocolType> {
+  return JSON.parse(parse_swarm(text))
+}
+
+export function printSwarmProtocol(proto: SwarmProtocolType): DataResult<string> {
+  return JSON.parse(print_swarm(JSON.stringify(proto)))
+}
+
 export function checkProjection(
   swarm: SwarmProtocolType,
   subscriptions: Subscriptions,
   role: string,
   machine: MachineType,
-): Result {
+): DataResult<Record<string, string[]>> {
   const sw = JSON.stringify(swarm)
   const sub = JSON.stringify(subscriptions)
   const m = JSON.stringify(machine)
   const result = check_projection(sw, sub, role, m)
   return JSON.parse(result)
 }
+
+export function checkRefinement(
+  swarm: SwarmProtocolType,
+  subscriptions: Subscriptions,
+  role: string,
+  machine: MachineType,
+): DataResult<Diagnostic[]> {
+  const sw = JSON.stringify(swarm)
+  const sub = JSON.stringify(subscriptions)
+  const m = JSON.stringify(machine)
+  const result = check_refinement(sw, sub, role, m)
+  return JSON.parse(result)
+}
+
+export function checkLiveness(swarm: SwarmProtocolType, subscriptions: Subscriptions): Result {
+  const sw = JSON.stringify(swarm)
+  const sub = JSON.stringify(subscriptions)
+  const result = check_liveness(sw, sub)
+  return JSON.parse(result)
+}
+
+export function checkStarvation(swarm: SwarmProtocolType, subscriptions: Subscriptions): Result {
+  const sw = JSON.stringify(swarm)
+  const sub = JSON.stringify(subscriptions)
+  const result = check_starvation(sw, sub)
+  return JSON.parse(result)
+}
+
+export type Compatibility = {
+  added: { source: string; target: string; label: SwarmLabel }[]
+  removed: { source: string; target: string; label: SwarmLabel }[]
+  relabelled: { source: string; target: string; old: SwarmLabel; new: SwarmLabel }[]
+  roles: Record<string, { unchanged: boolean; readsNewLogs: boolean; independent: boolean }>
+  diagnostics?: Diagnostic[]
+}
+
+export function checkCompatibility(
+  oldProtocol: SwarmProtocolType,
+  newProtocol: SwarmProtocolType,
+  oldSubscriptions: Subscriptions,
+  newSubscriptions: Subscriptions,
+): DataResult<Compatibility> {
+  const result = check_compatibility(
+    JSON.stringify(oldProtocol),
+    JSON.stringify(newProtocol),
+    JSON.stringify(oldSubscriptions),
+    JSON.stringify(newSubscriptions),
+  )
+  return JSON.parse(result)
+}
+
+export type Minimised<Label> = {
+  protocol: Protocol<Label>
+  /** maps each reachable state of the input to the state it was merged into */
+  mapping: Record<string, string>
+}
+
+export function minimiseSwarm(proto: SwarmProtocolType): DataResult<Minimised<SwarmLabel>> {
+  return JSON.parse(minimise_swarm(JSON.stringify(proto)))
+}
+
+export function minimiseMachine(machine: MachineType): DataResult<Minimised<MachineLabel>> {
+  return JSON.parse(minimise_machine(JSON.stringify(machine)))
+}
+
+export function composeProtocols(protocols: SwarmProtocolType[]): DataResult<SwarmProtocolType> {
+  return JSON.parse(compose_protocols(JSON.stringify(protocols)))
+}
+
+export function checkComposition(
+  protocols: SwarmProtocolType[],
+  subscriptions: Subscriptions[],
+): Result {
+  const p = JSON.stringify(protocols)
+  const s = JSON.stringify(subscriptions)
+  const result = check_composition(p, s)
+  return JSON.parse(result)
+}
+
+export function checkCompositionInterfaces(
+  protocols: SwarmProtocolType[],
+  subscriptions: Subscriptions[],
+): Result {
+  const p = JSON.stringify(protocols)
+  const s = JSON.stringify(subscriptions)
+  const result = check_composition_interfaces(p, s)
+  return JSON.parse(result)
+}
+
+export type Replay = {
+  state: string
+  pending?: { source: string; target: string; label: SwarmLabel }
+}
+
+export function replayLog(
+  swarm: SwarmProtocolType,
+  subscriptions: Subscriptions,
+  log: [eventType: string, role: string][],
+): DataResult<Replay> {
+  const sw = JSON.stringify(swarm)
+  const sub = JSON.stringify(subscriptions)
+  const result = replay_log(sw, sub, JSON.stringify(log))
+  return JSON.parse(result)
+}
+
+export function validatePayloads(
+  swarm: SwarmProtocolType,
+  log: [eventType: string, payload: unknown][],
+): Result {
+  const sw = JSON.stringify(swarm)
+  const result = validate_payloads(sw, JSON.stringify(log))
+  return JSON.parse(result)
+}
+
+export type Observation = { tag: 'Execute'; cmd: string } | { tag: 'Input'; eventType: string }
+
+export type MachineReplay = {
+  states: string[]
+  diagnostics?: Diagnostic[]
+}
+
+export function replayMachine(
+  swarm: SwarmProtocolType,
+  subscriptions: Subscriptions,
+  role: string,
+  history: Observation[],
+): DataResult<MachineReplay> {
+  const sw = JSON.stringify(swarm)
+  const sub = JSON.stringify(subscriptions)
+  const result = replay_machine(sw, sub, role, JSON.stringify(history))
+  return JSON.parse(result)
+}
+
+export type SimulationOptions = {
+  seed: number
+  /** number of commands after which the simulation stops, defaults to 20 */
+  maxSteps?: number
+  /** largest number of ticks an event may take to reach a subscriber, defaults to 0 */
+  maxDelay?: number
+  /** whether events may overtake each other on the way to a role */
+  reorder?: boolean
+}
+
+export type Simulation = {
+  seed: number
+  commands: { time: number; role: string; cmd: string; events: number[]; source: string; target: string }[]
+  log: [eventType: string, role: string][]
+  deliveries: { time: number; role: string; event: number; eventType: string; state: string }[]
+  state: string
+  roles: Record<string, string>
+}
+
+export function simulate(
+  swarm: SwarmProtocolType,
+  subscriptions: Subscriptions,
+  options: SimulationOptions,
+): DataResult<Simulation> {
+  const sw = JSON.stringify(swarm)
+  const sub = JSON.stringify(subscriptions)
+  const result = simulate_swarm(sw, sub, JSON.stringify(options))
+  return JSON.parse(result)
+}
+
+export type ConflictOptions = {
+  seed: number
+  /** state in which to issue the competing commands, defaults to the first one with a choice */
+  state?: string
+  /** largest difference between the clocks of the replicas, defaults to 3 */
+  maxSkew?: number
+}
+
+export type ConflictSimulation = {
+  seed: number
+  state: string
+  replicas: SwarmLabel[]
+  merged: { timestamp: number; replica?: number; eventType: string; role: string; accepted: boolean }[]
+  resolved: string
+  roles: Record<string, { expected: string; actual: string }>
+  diagnostics?: Diagnostic[]
+}
+
+export function simulateConflict(
+  swarm: SwarmProtocolType,
+  subscriptions: Subscriptions,
+  options: ConflictOptions,
+): DataResult<ConflictSimulation> {
+  const sw = JSON.stringify(swarm)
+  const sub = JSON.stringify(subscriptions)
+  const result = simulate_conflict(sw, sub, JSON.stringify(options))
+  return JSON.parse(result)
+}
+
+export type ExploreOptions = {
+  /** number of commands and deliveries after which the exploration stops, defaults to 8 */
+  maxDepth?: number
+  /** number of distinct configurations after which the exploration stops, defaults to 100000 */
+  maxStates?: number
+}
+
+export type ExploreStep =
+  | { tag: 'Execute'; role: string; label: MachineLabel; position: number }
+  | { tag: 'Deliver'; role: string; eventType: string; from: string }
+
+export type Exploration = {
+  states: number
+  truncated: boolean
+  diagnostics?: Diagnostic[]
+  witness?: ExploreStep[]
+}
+
+export function explore(
+  swarm: SwarmProtocolType,
+  subscriptions: Subscriptions,
+  options: ExploreOptions = {},
+): DataResult<Exploration> {
+  const sw = JSON.stringify(swarm)
+  const sub = JSON.stringify(subscriptions)
+  const result = explore_swarm(sw, sub, JSON.stringify(options))
+  return JSON.parse(result)
+}
@@ -1,12 +1,24 @@
 use std::collections::{BTreeMap, BTreeSet};
-use wasm_bindgen::prelude::*;
 
-mod machine;
-mod swarm;
+pub mod compatibility;
+pub mod composition;
+pub mod explore;
+pub mod export;
+pub mod liveness;
+pub mod machine;
+pub mod minimise;
+pub mod monitor;
+pub mod schema;
+pub mod simulation;
+pub mod swarm;
+pub mod text;
 pub mod types;
+#[cfg(feature = "wasm")]
+mod wasm;
 
-use petgraph::visit::GraphBase;
-use types::{CheckResult, EventType, MachineLabel, Protocol, Role, State, SwarmLabel};
+use itertools::Itertools;
+use petgraph::visit::{Dfs, GraphBase, GraphRef, Visitable};
+use types::{Diagnostic, ErrorCode, EventType, MachineLabel, Protocol, Role, State, SwarmLabel};
 
 pub type Subscriptions = BTreeMap<Role, BTreeSet<EventType>>;
 pub type SwarmProtocol = Protocol<SwarmLabel>;
@@ -16,71 +28,537 @@ pub type Graph = petgraph::Graph<State, SwarmLabel>;
 pub type NodeId = <petgraph::Graph<(), ()> as GraphBase>::NodeId;
 pub type EdgeId = <petgraph::Graph<(), ()> as GraphBase>::EdgeId;
 
-#[wasm_bindgen]
-pub fn check_swarm(proto: String, subs: String) -> String {
-    let proto = match serde_json::from_str::<SwarmProtocol>(&proto) {
-        Ok(p) => p,
-        Err(e) => return err(vec![format!("parsing swarm protocol: {}", e)]),
-    };
-    let subs = match serde_json::from_str::<Subscriptions>(&subs) {
-        Ok(p) => p,
-        Err(e) => return err(vec![format!("parsing subscriptions: {}", e)]),
+/// Check that the swarm protocol is well-formed with respect to the given subscriptions.
+///
+/// The `EdgeId`s mentioned in the errors are the indices of the offending transitions in
+/// `proto.transitions`; use [`swarm::check`] if you need the graph for rendering them.
+pub fn check_swarm(proto: &SwarmProtocol, subs: &Subscriptions) -> Result<(), Vec<swarm::Error>> {
+    let (_, _, errors) = swarm::check(proto.clone(), subs);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Compute the smallest extension of the given subscriptions that makes the swarm protocol
+/// well-formed as far as subscriptions are concerned, see [`swarm::infer_subscriptions`].
+pub fn infer_subscriptions(
+    proto: &SwarmProtocol,
+    subs: &Subscriptions,
+) -> Result<Subscriptions, Vec<swarm::Error>> {
+    swarm::infer_subscriptions(proto.clone(), subs)
+}
+
+/// Amend the subscriptions using the fixes attached to the errors of [`check_swarm`] until
+/// the swarm protocol passes that check, see [`swarm::suggest_subscription_fixes`].
+pub fn suggest_subscription_fixes(
+    proto: &SwarmProtocol,
+    subs: &Subscriptions,
+) -> Result<Subscriptions, Vec<swarm::Error>> {
+    swarm::suggest_subscription_fixes(proto.clone(), subs)
+}
+
+/// Check that the composition of the given swarm protocols is well-formed with respect to the
+/// union of the given subscriptions, see [`composition::compose`].
+///
+/// The diagnostics refer to the states and transitions of the composed protocol. Protocols that
+/// declare different payload schemas for the same event type are reported as well, and so is an
+/// empty list of protocols.
+pub fn check_composition(
+    protocols: &[SwarmProtocol],
+    subs: &[Subscriptions],
+) -> Result<(), Vec<Diagnostic>> {
+    let Some((graph, _, errors)) = composition::check(protocols, subs) else {
+        return Err(vec![Diagnostic::new(
+            ErrorCode::ParseError,
+            "no swarm protocols to compose".to_owned(),
+        )]);
     };
-    let (graph, _, errors) = swarm::check(proto, &subs);
+    let mut diagnostics = errors.map(swarm::Error::diagnostic(&graph));
+    diagnostics.extend(
+        composition::schema_mismatches(protocols)
+            .into_iter()
+            .map(composition::Error::diagnostic(&[])),
+    );
+    if diagnostics.is_empty() {
+        Ok(())
+    } else {
+        Err(diagnostics)
+    }
+}
+
+/// Check the composition like [`check_composition`], but without computing it, see
+/// [`composition::check_interfaces`].
+///
+/// The diagnostics refer to the states and transitions of the component protocols.
+pub fn check_composition_interfaces(
+    protocols: &[SwarmProtocol],
+    subs: &[Subscriptions],
+) -> Result<(), Vec<Diagnostic>> {
+    let (graphs, errors) = composition::check_interfaces(protocols, subs);
     if errors.is_empty() {
-        serde_json::to_string(&CheckResult::OK).unwrap()
+        Ok(())
     } else {
-        err(errors.map(swarm::Error::convert(&graph)))
+        Err(errors.map(composition::Error::diagnostic(&graphs)))
     }
 }
 
-#[wasm_bindgen]
-pub fn check_projection(swarm: String, subs: String, role: String, machine: String) -> String {
-    let swarm = match serde_json::from_str::<SwarmProtocol>(&swarm) {
-        Ok(p) => p,
-        Err(e) => return err(vec![format!("parsing swarm protocol: {}", e)]),
+/// Compute the machine that the given role should implement within the swarm protocol.
+///
+/// The swarm protocol itself is only checked for the properties needed to compute the projection,
+/// use [`check_swarm`] to check it fully.
+pub fn project_machine(
+    swarm: &SwarmProtocol,
+    subs: &Subscriptions,
+    role: Role,
+) -> Result<Machine, Vec<Diagnostic>> {
+    let finals = swarm.final_states.clone();
+    let schemas = swarm.schemas.clone();
+    let (swarm, initials, errors) = swarm::from_json(swarm.clone(), subs);
+    let Some(initials) = initials else {
+        return Err(errors);
+    };
+    let (proj, proj_initials) = machine::project(&swarm, &initials, subs, role);
+    let mut machine = machine::to_json(&proj, &proj_initials);
+    machine.final_states = proj
+        .node_weights()
+        .flatten()
+        .filter(|s| finals.contains(s))
+        .cloned()
+        .collect();
+    // keep the schemas of the event types the machine emits or consumes
+    machine.schemas = schemas
+        .into_iter()
+        .filter(|(ev, _)| {
+            proj.edge_weights().any(|l| match l {
+                MachineLabel::Execute { log_type, .. } => log_type.contains(ev),
+                MachineLabel::Input { event_type } => event_type == ev,
+            })
+        })
+        .collect();
+    Ok(machine)
+}
+
+/// Compare two revisions of a swarm protocol, see [`compatibility::check`].
+pub fn check_compatibility(
+    old: &SwarmProtocol,
+    new: &SwarmProtocol,
+    subs_old: &Subscriptions,
+    subs_new: &Subscriptions,
+) -> Result<compatibility::Compatibility, Vec<Diagnostic>> {
+    let (old, old_initials, mut errors) = swarm::from_json(old.clone(), subs_old);
+    let (new, new_initials, new_errors) = swarm::from_json(new.clone(), subs_new);
+    errors.extend(new_errors);
+    let (Some(old_initials), Some(new_initials)) = (old_initials, new_initials) else {
+        return Err(errors);
+    };
+    Ok(compatibility::check(
+        (&old, &old_initials, subs_old),
+        (&new, &new_initials, subs_new),
+    ))
+}
+
+/// Merge behaviourally equivalent states of the swarm protocol, see [`minimise`].
+pub fn minimise_swarm(
+    proto: &SwarmProtocol,
+) -> Result<minimise::Minimised<SwarmLabel>, Vec<Diagnostic>> {
+    let (swarm, initials, errors) = swarm::from_json(proto.clone(), &Subscriptions::new());
+    let Some(initials) = initials else {
+        return Err(errors);
     };
-    let subs = match serde_json::from_str::<Subscriptions>(&subs) {
-        Ok(p) => p,
-        Err(e) => return err(vec![format!("parsing subscriptions: {}", e)]),
+    let finals = final_nodes(&swarm, &proto.final_states, |s| Some(s));
+    let mut minimised = minimise::minimise_swarm(&swarm, &initials, &finals);
+    minimised.protocol.schemas = proto.schemas.clone();
+    Ok(minimised)
+}
+
+/// Merge behaviourally equivalent states of the machine, see [`minimise`].
+pub fn minimise_machine(
+    machine: &Machine,
+) -> Result<minimise::Minimised<MachineLabel>, Vec<Diagnostic>> {
+    let names = machine.final_states.clone();
+    let schemas = machine.schemas.clone();
+    let (machine, initials, mut errors) = machine::from_json(machine.clone());
+    let Some(initials) = initials else {
+        errors.push(Diagnostic::new(
+            ErrorCode::InitialMachineStateDisconnected,
+            "initial machine state has no transitions".to_owned(),
+        ));
+        return Err(errors);
     };
-    let role = Role::new(&role);
-    let machine = match serde_json::from_str::<Machine>(&machine) {
-        Ok(p) => p,
-        Err(e) => return err(vec![format!("parsing machine: {}", e)]),
+    let finals = final_nodes(&machine, &names, Option::as_ref);
+    let mut minimised = minimise::minimise_machine(&machine, &initials, &finals);
+    minimised.protocol.schemas = schemas;
+    Ok(minimised)
+}
+
+/// Render the swarm protocol in Graphviz DOT format, highlighting the problems found by
+/// [`check_swarm`].
+pub fn swarm_dot(proto: &SwarmProtocol, subs: &Subscriptions) -> String {
+    let (graph, initials, errors) = swarm::check(proto.clone(), subs);
+    let errors = errors.map(swarm::Error::diagnostic(&graph));
+    export::swarm_to_dot(&graph, &initials.unwrap_or_default(), &errors)
+}
+
+/// Render the projection of the swarm protocol onto the given role in Graphviz DOT format.
+pub fn projection_dot(
+    swarm: &SwarmProtocol,
+    subs: &Subscriptions,
+    role: Role,
+) -> Result<String, Vec<Diagnostic>> {
+    let (swarm, initials, errors) = swarm::from_json(swarm.clone(), subs);
+    let Some(initials) = initials else {
+        return Err(errors);
     };
+    let (proj, proj_initials) = machine::project(&swarm, &initials, subs, role);
+    Ok(export::machine_to_dot(&proj, &proj_initials, &[]))
+}
 
-    let (swarm, initial, mut errors) = swarm::from_json(swarm, &subs);
-    let Some(initial) = initial else {
-        return err(errors);
+/// Replay a recorded log of event types and the roles that emitted them against the swarm
+/// protocol, see [`monitor::replay`].
+///
+/// With several initial states the log may start in any of them; if it fits none, the problem
+/// found furthest into the log is reported.
+pub fn replay_log(
+    swarm: &SwarmProtocol,
+    subs: &Subscriptions,
+    log: &[(EventType, Role)],
+) -> Result<monitor::Replay, Vec<Diagnostic>> {
+    let (swarm, initials, errors) = swarm::from_json(swarm.clone(), subs);
+    let Some(initials) = initials else {
+        return Err(errors);
     };
-    let (proj, proj_initial) = machine::project(&swarm, initial, &subs, role);
-    let (machine, json_initial, m_errors) = machine::from_json(machine);
-    let machine_problem = !m_errors.is_empty();
-    errors.extend(m_errors);
-    let Some(json_initial) = json_initial else {
-        errors.push(format!("initial machine state has no transitions"));
-        return err(errors);
+    let mut furthest = Vec::<Diagnostic>::new();
+    for initial in initials {
+        match monitor::replay(&swarm, initial, log) {
+            Ok(replay) => return Ok(replay),
+            Err(errors) => {
+                let position = |e: &[Diagnostic]| e.first().and_then(|d| d.position);
+                if furthest.is_empty() || position(&errors) > position(&furthest) {
+                    furthest = errors;
+                }
+            }
+        }
+    }
+    Err(furthest)
+}
+
+/// Check the payloads of a recorded log against the schemas declared in the protocol, see
+/// [`schema::validate_log`].
+pub fn validate_payloads<L>(
+    proto: &Protocol<L>,
+    log: &[(EventType, serde_json::Value)],
+) -> Result<(), Vec<Diagnostic>> {
+    let diagnostics = schema::validate_log(&proto.schemas, log);
+    if diagnostics.is_empty() {
+        Ok(())
+    } else {
+        Err(diagnostics)
+    }
+}
+
+/// Replay the commands executed and events received by a machine for the given role against the
+/// projection of the swarm protocol, see [`monitor::replay_machine`].
+///
+/// With several initial states, the replay starting from the one that explains the history best
+/// (with the fewest problems) is returned.
+pub fn replay_machine(
+    swarm: &SwarmProtocol,
+    subs: &Subscriptions,
+    role: Role,
+    history: &[monitor::Observation],
+) -> Result<monitor::MachineReplay, Vec<Diagnostic>> {
+    let (swarm, initials, errors) = swarm::from_json(swarm.clone(), subs);
+    let Some(initials) = initials else {
+        return Err(errors);
     };
-    if machine_problem {
-        return err(errors);
+    let (proj, proj_initials) = machine::project(&swarm, &initials, subs, role);
+    let replays = proj_initials
+        .iter()
+        .map(|initial| monitor::replay_machine(&proj, *initial, history));
+    Ok(replays.min_by_key(|r| r.diagnostics.len()).unwrap())
+}
+
+/// Run a seeded random execution of the swarm protocol, see [`simulation::simulate`].
+pub fn simulate(
+    swarm: &SwarmProtocol,
+    subs: &Subscriptions,
+    options: &simulation::SimulationOptions,
+) -> Result<simulation::Simulation, Vec<Diagnostic>> {
+    let (swarm, initials, errors) = swarm::from_json(swarm.clone(), subs);
+    let Some(initials) = initials else {
+        return Err(errors);
+    };
+    Ok(simulation::simulate(&swarm, &initials, subs, options))
+}
+
+/// Simulate competing commands issued concurrently in the same state and how each role’s
+/// projection resolves the conflict, see [`simulation::simulate_conflict`].
+pub fn simulate_conflict(
+    swarm: &SwarmProtocol,
+    subs: &Subscriptions,
+    options: &simulation::ConflictOptions,
+) -> Result<simulation::ConflictSimulation, Vec<Diagnostic>> {
+    let (swarm, initials, errors) = swarm::from_json(swarm.clone(), subs);
+    let Some(initials) = initials else {
+        return Err(errors);
+    };
+    simulation::simulate_conflict(&swarm, &initials, subs, options)
+}
+
+/// Explore the interleavings of all projected machines under partial event delivery up to the
+/// configured depth, see [`explore::explore`].
+pub fn explore(
+    swarm: &SwarmProtocol,
+    subs: &Subscriptions,
+    options: &explore::ExploreOptions,
+) -> Result<explore::Exploration, Vec<Diagnostic>> {
+    let (swarm, initials, errors) = swarm::from_json(swarm.clone(), subs);
+    let Some(initials) = initials else {
+        return Err(errors);
+    };
+    Ok(explore::explore(&swarm, &initials, subs, options))
+}
+
+/// Check that the swarm protocol only ends in its final states, see [`liveness::check`].
+pub fn check_liveness(proto: &SwarmProtocol, subs: &Subscriptions) -> Result<(), Vec<Diagnostic>> {
+    let (swarm, initials, errors) = swarm::from_json(proto.clone(), subs);
+    let Some(initials) = initials else {
+        return Err(errors);
+    };
+    let diagnostics = liveness::check(&swarm, &initials, &proto.final_states, subs);
+    if diagnostics.is_empty() {
+        Ok(())
+    } else {
+        Err(diagnostics)
+    }
+}
+
+/// Find cycles in which a role may wait forever: the role has an enabled command in the cycle
+/// without executing any of its transitions, or it still subscribes to later events without
+/// receiving any from the cycle.
+///
+/// The swarm protocol does not need to be well-formed for this analysis.
+pub fn check_starvation(
+    proto: &SwarmProtocol,
+    subs: &Subscriptions,
+) -> Result<(), Vec<Diagnostic>> {
+    let (graph, initials, errors) = swarm::check_annotated(proto.clone(), subs);
+    let Some(initials) = initials else {
+        return Err(errors.map(swarm::Error::diagnostic(&graph)));
+    };
+    let diagnostics = liveness::starvation(&graph, &initials, subs);
+    if diagnostics.is_empty() {
+        Ok(())
+    } else {
+        Err(diagnostics)
+    }
+}
+
+/// Check that the given machine correctly implements the given role within the swarm protocol.
+///
+/// Both the projection and the machine are minimised before comparing them, so a machine may
+/// split a state of the projection into several equivalent ones (or vice versa). Each initial
+/// state of the swarm protocol must correspond to an initial state of the machine and vice versa,
+/// in any order.
+///
+/// The swarm protocol itself is only checked for the properties needed to compute the projection,
+/// use [`check_swarm`] to check it fully.
+///
+/// On success, returns the machine states corresponding to each state of the projection (named
+/// like in [`project_machine`], including intermediate states like `S1(+1)`). This is a single
+/// state each, unless the machine splits or merges states: then every state of the projection is
+/// mapped to all machine states that are equivalent to it.
+pub fn check_projection(
+    swarm: &SwarmProtocol,
+    subs: &Subscriptions,
+    role: Role,
+    machine: &Machine,
+) -> Result<BTreeMap<State, BTreeSet<State>>, Vec<Diagnostic>> {
+    let ((proj, proj_initials), (machine, json_initials), mut errors) =
+        projection_and_machine(swarm, subs, role, machine)?;
+    errors.extend(nondeterminism(
+        (&proj, &proj_initials),
+        (&machine, &json_initials),
+    ));
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let (min_proj, min_proj_initials, proj_map) = minimise::machine(&proj, &proj_initials, &[]);
+    let (min_machine, min_initials, machine_map) = minimise::machine(&machine, &json_initials, &[]);
+    let (m_errors, l2r, parents) =
+        machine::correspondence(&min_proj, &min_proj_initials, &min_machine, &min_initials);
+    errors.extend(m_errors.into_iter().map(machine::Error::diagnostic(
+        &min_proj,
+        &min_machine,
+        &parents,
+    )));
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let names = machine::state_names(&proj);
+    let (exact_errors, exact, _) =
+        machine::correspondence(&proj, &proj_initials, &machine, &json_initials);
+    if exact_errors.is_empty() && is_isomorphism(&proj, &machine, &exact) {
+        return Ok(proj
+            .node_indices()
+            .filter(|node| exact[node.index()] != NodeId::end())
+            .map(|node| {
+                let state = machine::state_name(&machine, exact[node.index()]).to_string();
+                (names[&node].clone(), BTreeSet::from([State::new(&state)]))
+            })
+            .collect());
     }
 
+    // states were split or merged, so only the classes of equivalent states correspond
+    let mut implemented_by = BTreeMap::<_, BTreeSet<_>>::new();
+    for node in machine.node_indices() {
+        if let Some(min) = machine_map[node.index()] {
+            let name = State::new(&machine::state_name(&machine, node).to_string());
+            implemented_by.entry(min).or_default().insert(name);
+        }
+    }
+    Ok(proj
+        .node_indices()
+        .filter_map(|node| {
+            let min = l2r[proj_map[node.index()]?.index()];
+            let states = implemented_by.get(&min).cloned().unwrap_or_default();
+            Some((names[&node].clone(), states))
+        })
+        .collect())
+}
+
+/// Check that the given machine safely implements the given role within the swarm protocol,
+/// possibly offering fewer commands than the projection.
+///
+/// The machine must handle all events of the projection in the states where they may arrive and
+/// must not offer commands the projection does not offer; it may react to additional events. On
+/// success, returns the commands of the projection that the machine does not offer
+/// ([`ErrorCode::CommandNotOffered`]). Otherwise all problems are returned, where those with
+/// other codes are the ones that make the machine unsafe.
+pub fn check_refinement(
+    swarm: &SwarmProtocol,
+    subs: &Subscriptions,
+    role: Role,
+    machine: &Machine,
+) -> Result<Vec<Diagnostic>, Vec<Diagnostic>> {
+    let ((proj, proj_initials), (machine, json_initials), mut errors) =
+        projection_and_machine(swarm, subs, role, machine)?;
+    let nondeterministic = nondeterminism((&proj, &proj_initials), (&machine, &json_initials));
+    if !nondeterministic.is_empty() {
+        errors.extend(nondeterministic);
+        return Err(errors);
+    }
+    let (proj, proj_initials, _) = minimise::machine(&proj, &proj_initials, &[]);
+    let (machine, json_initials, _) = minimise::machine(&machine, &json_initials, &[]);
+    let (m_errors, parents) = machine::refinement(&proj, &proj_initials, &machine, &json_initials);
+    let safe = errors.is_empty() && !m_errors.iter().any(|e| e.is_unsafe());
     errors.extend(
-        machine::equivalent(&proj, proj_initial, &machine, json_initial)
+        m_errors
             .into_iter()
-            .map(machine::Error::convert(&proj, &machine)),
+            .map(machine::Error::diagnostic(&proj, &machine, &parents)),
     );
+    if safe {
+        Ok(errors)
+    } else {
+        Err(errors)
+    }
+}
+
+type MachineGraph = (machine::Graph, Vec<NodeId>);
 
+/// Compute the projection and parse the machine, returning the problems with the swarm protocol
+/// that do not prevent this.
+fn projection_and_machine(
+    swarm: &SwarmProtocol,
+    subs: &Subscriptions,
+    role: Role,
+    machine: &Machine,
+) -> Result<(MachineGraph, MachineGraph, Vec<Diagnostic>), Vec<Diagnostic>> {
+    let (swarm, initials, mut errors) = swarm::from_json(swarm.clone(), subs);
+    let Some(initials) = initials else {
+        return Err(errors);
+    };
+    let (proj, proj_initials) = machine::project(&swarm, &initials, subs, role);
+    let (machine, json_initials, m_errors) = machine::from_json(machine.clone());
+    let machine_problem = !m_errors.is_empty();
+    errors.extend(m_errors);
+    let Some(json_initials) = json_initials else {
+        errors.push(Diagnostic::new(
+            ErrorCode::InitialMachineStateDisconnected,
+            "initial machine state has no transitions".to_owned(),
+        ));
+        return Err(errors);
+    };
+    if machine_problem {
+        return Err(errors);
+    }
+    Ok(((proj, proj_initials), (machine, json_initials), errors))
+}
+
+/// The nodes whose state name is listed as final
+fn final_nodes<N, E>(
+    graph: &petgraph::Graph<N, E>,
+    finals: &[State],
+    name: impl Fn(&N) -> Option<&State>,
+) -> Vec<NodeId> {
+    graph
+        .node_indices()
+        .filter(|n| name(&graph[*n]).is_some_and(|s| finals.contains(s)))
+        .collect()
+}
+
+/// Whether `l2r` maps the visited states of `left` one-to-one to states of `right` such that
+/// every transition of `left` has a counterpart with the same label between the mapped states.
+fn is_isomorphism(left: &machine::Graph, right: &machine::Graph, l2r: &[NodeId]) -> bool {
+    let visited = l2r.iter().filter(|r| **r != NodeId::end());
+    visited.clone().count() == visited.unique().count()
+        && left.edge_indices().all(|edge| {
+            let (source, target) = left.edge_endpoints(edge).unwrap();
+            let (rs, rt) = (l2r[source.index()], l2r[target.index()]);
+            rs == NodeId::end()
+                || right
+                    .edges_connecting(rs, rt)
+                    .any(|e| e.weight() == &left[edge])
+        })
+}
+
+/// Report the non-deterministic transitions of the projection and the machine before minimising
+/// them, since minimising would merge transitions with the same label that lead to equivalent
+/// states.
+fn nondeterminism(
+    (proj, proj_initials): (&machine::Graph, &[NodeId]),
+    (machine, json_initials): (&machine::Graph, &[NodeId]),
+) -> Vec<Diagnostic> {
+    let errors = machine::nondeterminism(proj, proj_initials, machine::Side::Left)
+        .into_iter()
+        .chain(machine::nondeterminism(
+            machine,
+            json_initials,
+            machine::Side::Right,
+        ))
+        .collect::<Vec<_>>();
     if errors.is_empty() {
-        serde_json::to_string(&CheckResult::OK).unwrap()
-    } else {
-        err(errors)
+        return Vec::new();
     }
+    // the traces lead along the pairs of states that the comparison would visit
+    let (_, _, parents) = machine::correspondence(proj, proj_initials, machine, json_initials);
+    errors
+        .into_iter()
+        .map(machine::Error::diagnostic(proj, machine, &parents))
+        .collect()
 }
 
-fn err(errors: Vec<String>) -> String {
-    serde_json::to_string(&CheckResult::ERROR { errors }).unwrap()
+/// Depth-first traversal from several start nodes, visiting them in the given order.
+fn dfs<G>(graph: G, starts: &[NodeId]) -> Dfs<NodeId, G::Map>
+where
+    G: GraphRef + Visitable<NodeId = NodeId>,
+{
+    let mut dfs = Dfs::empty(graph);
+    dfs.stack.extend(starts.iter().rev());
+    dfs
 }
 
 trait MapVec<T> {
@@ -0,0 +1,289 @@
+//! Checking that a swarm protocol ends only where it is meant to
+//!
+//! States listed as `final` in the protocol are deliberate end states. All other reachable states
+//! must have a way forward: a non-final state without outgoing transitions is a deadlock, and a
+//! state from which no final state can be reached means that the swarm may run forever without
+//! completing. Each role must also end up in a final state: if the projection of a role stops in
+//! a non-final state, the role will wait for events that it never receives.
+//!
+//! Even a protocol that can always complete may keep a role waiting forever, e.g. when others
+//! can repeat a loop of transitions indefinitely. [`crate::check_starvation`] reports
+//! such cycles per role.
+
+use crate::{
+    dfs, machine, swarm,
+    types::{Diagnostic, ErrorCode, Role, State},
+    Graph, NodeId, Subscriptions,
+};
+use itertools::Itertools;
+use petgraph::{
+    algo::tarjan_scc,
+    visit::{EdgeRef, Reversed, Walker},
+    Direction::Outgoing,
+};
+use std::collections::BTreeSet;
+
+/// Check the swarm protocol for unintended end states, see the module documentation.
+///
+/// Without any final states every state without outgoing transitions is reported, and the
+/// check whether a final state remains reachable is skipped.
+pub fn check(
+    swarm: &Graph,
+    initials: &[NodeId],
+    finals: &[State],
+    subs: &Subscriptions,
+) -> Vec<Diagnostic> {
+    let _span = tracing::debug_span!("liveness").entered();
+    let mut diagnostics = Vec::new();
+
+    let final_nodes = swarm
+        .node_indices()
+        .filter(|n| finals.contains(&swarm[*n]))
+        .collect::<Vec<_>>();
+    for state in finals {
+        if !final_nodes.iter().any(|n| swarm[*n] == *state) {
+            let mut d = Diagnostic::new(
+                ErrorCode::FinalStateUnknown,
+                format!("final state {state} is not part of the protocol"),
+            );
+            d.state = Some(state.clone());
+            diagnostics.push(d);
+        }
+    }
+
+    let reachable = dfs(swarm, initials).iter(swarm).collect::<Vec<_>>();
+    let deadlocks = reachable
+        .iter()
+        .filter(|n| {
+            !final_nodes.contains(n) && swarm.edges_directed(**n, Outgoing).next().is_none()
+        })
+        .copied()
+        .collect::<BTreeSet<_>>();
+    for node in &deadlocks {
+        let state = &swarm[*node];
+        let mut d = Diagnostic::new(
+            ErrorCode::NonFinalDeadlock,
+            format!("state {state} is not final but has no outgoing transitions"),
+        );
+        d.state = Some(state.clone());
+        diagnostics.push(d);
+    }
+
+    if !final_nodes.is_empty() {
+        let reversed = Reversed(swarm);
+        let completing = dfs(reversed, &final_nodes)
+            .iter(reversed)
+            .collect::<BTreeSet<_>>();
+        for node in &reachable {
+            if completing.contains(node) || deadlocks.contains(node) {
+                continue;
+            }
+            let state = &swarm[*node];
+            let mut d = Diagnostic::new(
+                ErrorCode::FinalStateUnreachable,
+                format!("no final state is reachable from state {state}"),
+            );
+            d.state = Some(state.clone());
+            diagnostics.push(d);
+        }
+    }
+
+    // deadlocks of the swarm are deadlocks of the projections as well, no need to repeat them
+    let deadlock_names = deadlocks
+        .iter()
+        .map(|n| &swarm[*n])
+        .collect::<BTreeSet<_>>();
+    let roles = subs
+        .keys()
+        .chain(swarm.edge_weights().map(|l| &l.role))
+        .cloned()
+        .collect::<BTreeSet<Role>>();
+    for role in roles {
+        let (proj, proj_initials) = machine::project(swarm, initials, subs, role.clone());
+        for node in dfs(&proj, &proj_initials).iter(&proj) {
+            if proj.edges_directed(node, Outgoing).next().is_some() {
+                continue;
+            }
+            let Some(state) = &proj[node] else {
+                continue;
+            };
+            if finals.contains(state) || deadlock_names.contains(state) {
+                continue;
+            }
+            let mut d = Diagnostic::new(
+                ErrorCode::ProjectionNotFinal,
+                format!("role {role} ends in non-final state {state}"),
+            );
+            d.state = Some(state.clone());
+            d.roles.push(role.clone());
+            diagnostics.push(d);
+        }
+    }
+
+    diagnostics
+}
+
+/// Find cycles of the swarm protocol that may repeat forever without involving some role.
+///
+/// A cycle is a strongly connected component with at least one transition; only those reachable
+/// from the initial states are considered. A role can be starved in such a cycle if
+///
+/// - it has an enabled command in one of its states but none of the cycle’s transitions is
+///   executed by it, or
+/// - it still subscribes to events emitted later on but none of the cycle’s transitions emits
+///   such an event.
+///
+/// Starvation is only possible, not certain: it depends on the other roles choosing to stay in
+/// the cycle.
+pub(crate) fn starvation(
+    graph: &swarm::Graph,
+    initials: &[NodeId],
+    subs: &Subscriptions,
+) -> Vec<Diagnostic> {
+    let _span = tracing::debug_span!("starvation").entered();
+    let empty = BTreeSet::new();
+    let sub = |r: &Role| subs.get(r).unwrap_or(&empty);
+    let reachable = dfs(graph, initials).iter(graph).collect::<BTreeSet<_>>();
+
+    let mut cycles = tarjan_scc(graph)
+        .into_iter()
+        .filter(|scc| reachable.contains(&scc[0]))
+        .map(|mut scc| {
+            scc.sort();
+            scc
+        })
+        .collect::<Vec<_>>();
+    cycles.sort();
+
+    let mut diagnostics = Vec::new();
+    for scc in cycles {
+        let edges = scc
+            .iter()
+            .flat_map(|n| graph.edges_directed(*n, Outgoing))
+            .filter(|e| scc.contains(&e.target()))
+            .map(|e| e.weight())
+            .collect::<Vec<_>>();
+        if edges.is_empty() {
+            continue;
+        }
+        let states = scc.iter().map(|n| &graph[*n].name).join(",");
+        tracing::debug!("checking cycle {{{states}}}");
+        let waiting = scc
+            .iter()
+            .flat_map(|n| graph[*n].active.iter().chain(&graph[*n].roles))
+            .collect::<BTreeSet<_>>();
+        for role in waiting {
+            let acts = edges.iter().any(|l| l.role == *role);
+            let receives = edges
+                .iter()
+                .any(|l| l.log_type.iter().any(|ev| sub(role).contains(ev)));
+            let active = scc.iter().any(|n| graph[*n].active.contains(role));
+            let involved = scc.iter().any(|n| graph[*n].roles.contains(role));
+            if (active && !acts) || (involved && !receives) {
+                let mut d = Diagnostic::new(
+                    ErrorCode::RoleStarved,
+                    format!("role {role} can be starved in cycle {{{states}}}"),
+                );
+                d.state = Some(graph[scc[0]].name.clone());
+                d.roles.push(role.clone());
+                diagnostics.push(d);
+            }
+        }
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{text::parse_swarm, types::State, Subscriptions};
+    use pretty_assertions::assert_eq;
+
+    fn messages(result: Result<(), Vec<crate::types::Diagnostic>>) -> Vec<String> {
+        result
+            .unwrap_err()
+            .into_iter()
+            .map(|d| format!("{:?}: {}", d.code, d.message))
+            .collect()
+    }
+
+    #[test]
+    fn deadlocks() {
+        let mut proto = parse_swarm(
+            "initial Idle\n\
+             final Done\n\
+             (Idle) --[start@A<started>]--> (Busy)\n\
+             (Busy) --[finish@A<finished>]--> (Done)\n\
+             (Busy) --[fail@B<failed>]--> (Broken)\n\
+             (Idle) --[spin@B<spun>]--> (Spinning)\n\
+             (Spinning) --[spin@B<spun>]--> (Spinning)\n",
+        )
+        .unwrap();
+        let subs: Subscriptions = serde_json::from_str(
+            r#"{
+                "A": ["started", "finished"],
+                "B": ["started", "failed", "spun"],
+                "C": ["started"]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            messages(crate::check_liveness(&proto, &subs)),
+            vec![
+                "NonFinalDeadlock: state Broken is not final but has no outgoing transitions",
+                "FinalStateUnreachable: no final state is reachable from state Spinning",
+                "ProjectionNotFinal: role C ends in non-final state Busy",
+            ]
+        );
+
+        let machine = crate::project_machine(&proto, &subs, crate::Role::new("A")).unwrap();
+        assert_eq!(machine.final_states, vec![State::new("Done")]);
+
+        proto.final_states = vec![State::new("Done"), State::new("Finished")];
+        assert_eq!(
+            messages(crate::check_liveness(&proto, &subs))[0],
+            "FinalStateUnknown: final state Finished is not part of the protocol"
+        );
+
+        proto.final_states = vec![];
+        assert_eq!(
+            messages(crate::check_liveness(&proto, &subs)),
+            vec![
+                "NonFinalDeadlock: state Done is not final but has no outgoing transitions",
+                "NonFinalDeadlock: state Broken is not final but has no outgoing transitions",
+                "ProjectionNotFinal: role C ends in non-final state Busy",
+            ]
+        );
+    }
+
+    #[test]
+    fn starvation() {
+        let proto = parse_swarm(
+            "initial S0\n\
+             (S0) --[request@P<Requested>]--> (S1)\n\
+             (S1) --[offer@T<Bid>]--> (S2)\n\
+             (S2) --[offer@T<Bid>]--> (S3)\n\
+             (S3) --[withdraw@T<Withdrawn>]--> (S2)\n\
+             (S2) --[select@P<Selected>]--> (S4)\n\
+             (S4) --[ride@T<Arrived>]--> (S4)\n\
+             (S4) --[pay@P<Paid>]--> (S5)\n",
+        )
+        .unwrap();
+        let subs: Subscriptions = serde_json::from_str(
+            r#"{
+                "P": ["Requested", "Bid", "Selected", "Arrived", "Paid"],
+                "T": ["Requested", "Bid", "Withdrawn", "Selected", "Arrived", "Paid"],
+                "O": ["Selected", "Paid"]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            messages(crate::check_starvation(&proto, &subs)),
+            vec![
+                "RoleStarved: role O can be starved in cycle {S2,S3}",
+                "RoleStarved: role P can be starved in cycle {S2,S3}",
+                "RoleStarved: role O can be starved in cycle {S4}",
+                "RoleStarved: role P can be starved in cycle {S4}",
+            ]
+        );
+    }
+}
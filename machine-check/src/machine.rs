@@ -1,28 +1,34 @@
 use crate::{
-    types::{Command, EventType, MachineLabel, Role, State},
+    dfs,
+    types::{
+        Command, Diagnostic, DiagnosticLabel, ErrorCode, EventType, MachineLabel, Role, State,
+        TraceStep, Transition,
+    },
     EdgeId, Machine, NodeId, Subscriptions,
 };
 use itertools::Itertools;
 use petgraph::{
-    visit::{Dfs, EdgeFiltered, EdgeRef, IntoEdgeReferences, IntoEdgesDirected, Walker},
+    visit::{EdgeFiltered, EdgeRef, IntoEdgeReferences, IntoEdgesDirected, Walker},
     Direction::{Incoming, Outgoing},
 };
 use std::{
     cmp::Ordering,
-    collections::{BTreeMap, BTreeSet, HashMap},
+    collections::{btree_map::Entry, BTreeMap, BTreeSet, HashMap, HashSet},
     fmt,
     iter::once,
 };
 
-type Graph = petgraph::Graph<Option<State>, MachineLabel>;
+pub type Graph = petgraph::Graph<Option<State>, MachineLabel>;
 type ERef<'a> = <&'a super::Graph as IntoEdgeReferences>::EdgeRef;
 
+/// Compute the machine for the given role, with one initial state per initial state of the swarm
+/// protocol (in the same order, so that they can be told apart when they are equivalent).
 pub fn project(
     swarm: &super::Graph,
-    initial: NodeId,
+    initials: &[NodeId],
     subs: &Subscriptions,
     role: Role,
-) -> (Graph, NodeId) {
+) -> (Graph, Vec<NodeId>) {
     let _span = tracing::debug_span!("project", %role).entered();
     /*
      * Machine will be a graph containing:
@@ -41,7 +47,7 @@ pub fn project(
     // need to keep track of corresponding machine node for each swarm node
     let mut m_nodes = vec![NodeId::end(); swarm.node_count()];
     // first loop creates all relevant (corresponding) nodes and transfers commands
-    for s_node in Dfs::new(&filtered, initial).iter(&filtered) {
+    for s_node in dfs(&filtered, initials).iter(&filtered) {
         tracing::debug!("adding state {} {s_node:?}", swarm[s_node]);
         let m_node = machine.add_node(Some(swarm[s_node].clone()));
         m_nodes[s_node.index()] = m_node;
@@ -62,11 +68,15 @@ pub fn project(
     }
     tracing::debug!("nodes created");
     // second loop inserts all event input edges since now the node mapping is complete
-    for s_node in Dfs::new(&filtered, initial).iter(&filtered) {
+    for s_node in dfs(&filtered, initials).iter(&filtered) {
         tracing::debug!("adding transitions into state {}", swarm[s_node]);
         let m_node = m_nodes[s_node.index()];
         for edge in filtered.edges_directed(s_node, Incoming) {
             let start = m_nodes[edge.source().index()];
+            if start == NodeId::end() {
+                // source state is not reachable from the initial states
+                continue;
+            }
             let log = edge.weight().log_type.iter().filter(|ev| sub.contains(*ev));
             let evs = log.clone().count();
             // we need to turn a log of length N into N transitions, i.e. we need N-1 synthetic intermediate states
@@ -89,10 +99,61 @@ pub fn project(
             }
         }
     }
-    (machine, m_nodes[initial.index()])
+    let initials = initials.iter().map(|i| m_nodes[i.index()]).collect();
+    (machine, initials)
+}
+
+/// Turn a machine graph (e.g. a projection) back into its JSON representation.
+///
+/// Synthetic intermediate states are named after the named state they follow, like in error
+/// messages (e.g. `S1(+1)`), with a numeric suffix where this would be ambiguous.
+pub fn to_json(machine: &Graph, initials: &[NodeId]) -> Machine {
+    let names = state_names(machine);
+    let transitions = machine
+        .edge_references()
+        .map(|edge| Transition {
+            label: edge.weight().clone(),
+            source: names[&edge.source()].clone(),
+            target: names[&edge.target()].clone(),
+        })
+        .collect();
+    Machine {
+        initial: initials.iter().unique().map(|n| names[n].clone()).collect(),
+        transitions,
+        final_states: Vec::new(),
+        schemas: BTreeMap::new(),
+    }
+}
+
+/// The names used by [`to_json`] for all states of the machine.
+pub(crate) fn state_names(machine: &Graph) -> HashMap<NodeId, State> {
+    let mut used = machine
+        .node_weights()
+        .flatten()
+        .cloned()
+        .collect::<HashSet<_>>();
+    let mut names = HashMap::new();
+    for node in machine.node_indices() {
+        let name = match &machine[node] {
+            Some(state) => state.clone(),
+            None => {
+                let base = state_name(machine, node).to_string();
+                let mut name = State::new(&base);
+                let mut n = 1;
+                while !used.insert(name.clone()) {
+                    n += 1;
+                    name = State::new(&format!("{base} #{n}"));
+                }
+                name
+            }
+        };
+        names.insert(node, name);
+    }
+    names
 }
 
-pub fn from_json(proto: Machine) -> (Graph, Option<NodeId>, Vec<String>) {
+/// Build the machine graph, returning its initial states unless some of them have no transitions.
+pub fn from_json(proto: Machine) -> (Graph, Option<Vec<NodeId>>, Vec<Diagnostic>) {
     let _span = tracing::debug_span!("from_json").entered();
     let mut errors = Vec::new();
     let mut machine = Graph::new();
@@ -105,28 +166,46 @@ pub fn from_json(proto: Machine) -> (Graph, Option<NodeId>, Vec<String>) {
         let target = *nodes
             .entry(t.target.clone())
             .or_insert_with(|| machine.add_node(Some(t.target)));
-        if let (MachineLabel::Execute { cmd, .. }, true) = (&t.label, source != target) {
-            errors.push(format!(
-                "command {cmd} is not a self-loop in state {}",
-                t.source
-            ));
+        let edge = machine.add_edge(source, target, t.label.clone());
+        if let (MachineLabel::Execute { cmd, log_type }, true) = (&t.label, source != target) {
+            let mut d = Diagnostic::new(
+                ErrorCode::CommandNotSelfLoop,
+                format!("command {cmd} is not a self-loop in state {}", t.source),
+            );
+            d.state = Some(t.source);
+            d.transition = transition(&machine, edge);
+            d.event_types = log_type.clone();
+            errors.push(d);
         }
-        machine.add_edge(source, target, t.label);
     }
-    (machine, nodes.get(&proto.initial).copied(), errors)
+    let initials = proto
+        .initial
+        .iter()
+        .map(|state| nodes.get(state).copied())
+        .collect::<Option<Vec<_>>>()
+        .filter(|initials| !initials.is_empty());
+    (machine, initials, errors)
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Side {
     Left,
     Right,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Error {
     /// The given edge’s label is not unique for this side: a machine can have only one reaction
     /// to a given event or one handler for a given command
     NonDeterministic(Side, EdgeId),
     /// The given side in the given node is missing the edge from the OTHER side
     MissingTransition(Side, NodeId, EdgeId),
+    /// The specimen in the given node does not offer the command of the given reference edge,
+    /// which is allowed when checking for refinement
+    OmittedCommand(NodeId, EdgeId),
+    /// The given side has no initial state corresponding to the given initial node of the OTHER
+    /// side, see [`correspondence`]
+    MissingInitialState(Side, NodeId),
 }
 
 impl Error {
@@ -134,7 +213,7 @@ impl Error {
         match self {
             Error::NonDeterministic(Side::Left, edge) => {
                 let Some((state, _)) = left.edge_endpoints(*edge) else {
-                    return format!("non-deterministic transition in reference");
+                    return "non-deterministic transition in reference".to_string();
                 };
                 let state = state_name(left, state);
                 let label = left.edge_weight(*edge).unwrap();
@@ -142,7 +221,7 @@ impl Error {
             }
             Error::NonDeterministic(Side::Right, edge) => {
                 let Some((state, _)) = right.edge_endpoints(*edge) else {
-                    return format!("non-deterministic transition in specimen");
+                    return "non-deterministic transition in specimen".to_string();
                 };
                 let state = state_name(right, state);
                 let label = right.edge_weight(*edge).unwrap();
@@ -165,15 +244,158 @@ impl Error {
                 let label = left.edge_weight(*l_edge).unwrap();
                 format!("missing transition {label} in state {state} (from reference state {from})")
             }
+            Error::OmittedCommand(r_node, l_edge) => {
+                let state = state_name(right, *r_node);
+                let Some((from, _)) = left.edge_endpoints(*l_edge) else {
+                    return format!("command not offered in {state}");
+                };
+                let from = state_name(left, from);
+                let label = left.edge_weight(*l_edge).unwrap();
+                format!(
+                    "command {label} not offered in state {state} (from reference state {from})"
+                )
+            }
+            Error::MissingInitialState(Side::Left, r_node) => {
+                let state = state_name(right, *r_node);
+                format!("extraneous initial state {state}")
+            }
+            Error::MissingInitialState(Side::Right, l_node) => {
+                let state = state_name(left, *l_node);
+                format!("missing initial state corresponding to reference state {state}")
+            }
         }
     }
 
     pub fn convert<'a>(left: &'a Graph, right: &'a Graph) -> impl Fn(Error) -> String + 'a {
         |err| err.to_string(left, right)
     }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Error::NonDeterministic(..) => ErrorCode::NonDeterministicTransition,
+            Error::MissingTransition(Side::Left, ..) => ErrorCode::ExtraneousTransition,
+            Error::MissingTransition(Side::Right, ..) => ErrorCode::MissingTransition,
+            Error::OmittedCommand(..) => ErrorCode::CommandNotOffered,
+            Error::MissingInitialState(..) => ErrorCode::InitialStateMismatch,
+        }
+    }
+
+    /// Whether this error makes the specimen unsafe to use, as opposed to merely less capable
+    pub fn is_unsafe(&self) -> bool {
+        !matches!(self, Error::OmittedCommand(..))
+    }
+
+    /// Sequence of transitions along which the comparison that found this error reached the
+    /// states in question, starting from a pair of initial states (the shortest one if the
+    /// states were reached several times).
+    pub fn trace(&self, left: &Graph, right: &Graph, parents: &Parents) -> Vec<TraceStep> {
+        let source = |g: &Graph, edge: EdgeId| g.edge_endpoints(edge).map(|(s, _)| s);
+        let (l_goal, r_goal) = match self {
+            Error::NonDeterministic(Side::Left, edge) => (source(left, *edge), None),
+            Error::NonDeterministic(Side::Right, edge) => (None, source(right, *edge)),
+            Error::MissingTransition(Side::Left, l_node, r_edge) => {
+                (Some(*l_node), source(right, *r_edge))
+            }
+            Error::MissingTransition(Side::Right, r_node, l_edge)
+            | Error::OmittedCommand(r_node, l_edge) => (source(left, *l_edge), Some(*r_node)),
+            Error::MissingInitialState(..) => return Vec::new(),
+        };
+        let is_goal = |(l, r): (NodeId, NodeId)| {
+            l_goal.iter().all(|g| *g == l) && r_goal.iter().all(|g| *g == r)
+        };
+
+        parents
+            .0
+            .keys()
+            .filter(|pair| is_goal(**pair))
+            .map(|pair| {
+                let mut trace = Vec::new();
+                let mut current = *pair;
+                while let Some(Some((prev, edge))) = parents.0.get(&current) {
+                    trace.push(TraceStep {
+                        label: left[*edge].clone(),
+                        reference: State::new(&state_name(left, current.0).to_string()),
+                        specimen: State::new(&state_name(right, current.1).to_string()),
+                    });
+                    current = *prev;
+                }
+                trace.reverse();
+                trace
+            })
+            .min_by_key(|trace| trace.len())
+            .unwrap_or_default()
+    }
+
+    pub fn to_diagnostic(&self, left: &Graph, right: &Graph, parents: &Parents) -> Diagnostic {
+        let mut d = Diagnostic::new(self.code(), self.to_string(left, right));
+        d.trace = self.trace(left, right, parents);
+        let (state, transition) = match self {
+            Error::NonDeterministic(Side::Left, edge) => {
+                let state = left.edge_endpoints(*edge).map(|(s, _)| state_name(left, s));
+                (state, transition(left, *edge))
+            }
+            Error::NonDeterministic(Side::Right, edge) => {
+                let state = right
+                    .edge_endpoints(*edge)
+                    .map(|(s, _)| state_name(right, s));
+                (state, transition(right, *edge))
+            }
+            Error::MissingTransition(Side::Left, l_node, r_edge) => {
+                (Some(state_name(left, *l_node)), transition(right, *r_edge))
+            }
+            Error::MissingTransition(Side::Right, r_node, l_edge)
+            | Error::OmittedCommand(r_node, l_edge) => {
+                (Some(state_name(right, *r_node)), transition(left, *l_edge))
+            }
+            Error::MissingInitialState(Side::Left, r_node) => {
+                (Some(state_name(right, *r_node)), None)
+            }
+            Error::MissingInitialState(Side::Right, l_node) => {
+                (Some(state_name(left, *l_node)), None)
+            }
+        };
+        d.state = state.map(|s| State::new(&s.to_string()));
+        if let Some(Transition {
+            label: DiagnosticLabel::Machine(label),
+            ..
+        }) = &transition
+        {
+            d.event_types = match label {
+                MachineLabel::Execute { log_type, .. } => log_type.clone(),
+                MachineLabel::Input { event_type } => vec![event_type.clone()],
+            };
+        }
+        d.transition = transition;
+        d
+    }
+
+    pub fn diagnostic<'a>(
+        left: &'a Graph,
+        right: &'a Graph,
+        parents: &'a Parents,
+    ) -> impl Fn(Error) -> Diagnostic + 'a {
+        move |err| err.to_diagnostic(left, right, parents)
+    }
 }
 
-struct StatePrinter<'a>(Option<&'a State>, u32);
+/// The pairs of states visited while comparing two machines, each with the pair it was reached
+/// from and the transition of the left machine that was taken (`None` for initial states).
+#[derive(Debug, Default)]
+pub struct Parents(BTreeMap<Pair, Option<(Pair, EdgeId)>>);
+
+/// a state of the left machine and one of the right machine
+type Pair = (NodeId, NodeId);
+
+fn transition(g: &Graph, edge: EdgeId) -> Option<Transition<DiagnosticLabel>> {
+    let (source, target) = g.edge_endpoints(edge)?;
+    Some(Transition {
+        label: DiagnosticLabel::Machine(g[edge].clone()),
+        source: State::new(&state_name(g, source).to_string()),
+        target: State::new(&state_name(g, target).to_string()),
+    })
+}
+
+pub(crate) struct StatePrinter<'a>(Option<&'a State>, u32);
 
 impl<'a> fmt::Display for StatePrinter<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -185,7 +407,7 @@ impl<'a> fmt::Display for StatePrinter<'a> {
     }
 }
 
-fn state_name(g: &Graph, mut n: NodeId) -> StatePrinter<'_> {
+pub(crate) fn state_name(g: &Graph, mut n: NodeId) -> StatePrinter<'_> {
     let mut offset = 0;
     loop {
         match g.node_weight(n) {
@@ -218,20 +440,62 @@ impl From<&MachineLabel> for DeterministicLabel {
 }
 
 /// error messages are designed assuming that `left` is the reference and `right` the tested
-pub fn equivalent(left: &Graph, li: NodeId, right: &Graph, ri: NodeId) -> Vec<Error> {
-    use Side::*;
+///
+/// The initial states `li` and `ri` are sets: each initial state of `left` must be equivalent to
+/// some initial state of `right` and vice versa, regardless of the order in which they are listed.
+pub fn equivalent(left: &Graph, li: &[NodeId], right: &Graph, ri: &[NodeId]) -> Vec<Error> {
+    correspondence(left, li, right, ri).0
+}
 
+/// Same as [`equivalent`], but also returns the node of `right` that was visited together with
+/// each node of `left` (`NodeId::end()` for nodes that were not visited) and the visited pairs
+/// for tracing the errors.
+///
+/// For deterministic machines without state redundancy (see [`crate::minimise`]) that are
+/// equivalent, this is a bijection between the reachable states.
+pub fn correspondence(
+    left: &Graph,
+    li: &[NodeId],
+    right: &Graph,
+    ri: &[NodeId],
+) -> (Vec<Error>, Vec<NodeId>, Parents) {
     let _span = tracing::debug_span!("equivalent").entered();
 
+    let (pairs, mut errors) = initial_pairs(left, li, right, ri, |l, r| {
+        let mut parents = Parents::default();
+        walk_equivalent(left, right, &[(l, r)], &mut parents)
+            .0
+            .is_empty()
+    });
+    let mut parents = Parents::default();
+    let (walk_errors, l2r) = walk_equivalent(left, right, &pairs, &mut parents);
+    errors.extend(walk_errors);
+    (errors, l2r, parents)
+}
+
+/// compare the states reachable from the given pairs of initial states
+fn walk_equivalent(
+    left: &Graph,
+    right: &Graph,
+    pairs: &[(NodeId, NodeId)],
+    parents: &mut Parents,
+) -> (Vec<Error>, Vec<NodeId>) {
+    use Side::*;
+
     let mut errors = Vec::new();
     let mut l2r = vec![NodeId::end(); left.node_count()];
     let mut r2l = vec![NodeId::end(); right.node_count()];
 
     // dfs traversal stack
-    // must hold index pairs because node mappings might be m:n
-    let mut stack = vec![(li, ri)];
+    // must hold index pairs because node mappings might be m:n, together with where they came from
+    let mut stack = pairs
+        .iter()
+        .map(|pair| (*pair, None))
+        .rev()
+        .collect::<Vec<_>>();
 
-    while let Some((li, ri)) = stack.pop() {
+    while let Some(((li, ri), parent)) = stack.pop() {
+        parents.0.entry((li, ri)).or_insert(parent);
         tracing::debug!(left = %state_name(left, li), ?li, right = %state_name(right, ri), ?ri, to_go = stack.len(), "loop");
         // get all outgoing edge labels for the left side
         let mut l_out = BTreeMap::new();
@@ -288,7 +552,7 @@ pub fn equivalent(left: &Graph, li: NodeId, right: &Graph, ri: NodeId) -> Vec<Er
                         let rt = r_edge.target();
                         if l2r[lt.index()] == NodeId::end() || r2l[rt.index()] == NodeId::end() {
                             tracing::debug!(?lt, ?rt, "pushing targets");
-                            stack.push((lt, rt));
+                            stack.push(((lt, rt), Some(((li, ri), l_edge.id()))));
                         }
                         l_edges.next();
                         r_edges.next();
@@ -309,11 +573,164 @@ pub fn equivalent(left: &Graph, li: NodeId, right: &Graph, ri: NodeId) -> Vec<Er
         }
     }
 
+    (errors, l2r)
+}
+
+/// Report the transitions of the states reachable from `initials` whose command or event type is
+/// already handled by another transition of the same state.
+///
+/// Minimising a machine merges such transitions if they lead to equivalent states, so this must be
+/// checked on the original machine.
+pub fn nondeterminism(graph: &Graph, initials: &[NodeId], side: Side) -> Vec<Error> {
+    let mut errors = Vec::new();
+    for node in dfs(graph, initials).iter(graph) {
+        let mut labels = BTreeSet::new();
+        for edge in graph.edges_directed(node, Outgoing) {
+            if !labels.insert(DeterministicLabel::from(edge.weight())) {
+                errors.push(Error::NonDeterministic(side, edge.id()));
+            }
+        }
+    }
+    errors
+}
+
+/// Pair every initial state of `left` with an initial state of `right` that `fits` it.
+///
+/// If there is none, the initial state of `right` with the same name (or else at the same
+/// position) is used so that the differences get reported, and only if that does not exist either
+/// the initial state is reported as missing. Initial states of `right` that fit no initial state of
+/// `left` and were not paired with one are extraneous.
+fn initial_pairs(
+    left: &Graph,
+    li: &[NodeId],
+    right: &Graph,
+    ri: &[NodeId],
+    fits: impl Fn(NodeId, NodeId) -> bool,
+) -> (Vec<(NodeId, NodeId)>, Vec<Error>) {
+    let mut pairs = Vec::new();
+    let mut errors = Vec::new();
+    for (idx, l) in li.iter().enumerate() {
+        let name = state_name(left, *l).to_string();
+        let paired = ri
+            .iter()
+            .find(|r| fits(*l, **r))
+            .or_else(|| {
+                ri.iter()
+                    .find(|r| state_name(right, **r).to_string() == name)
+            })
+            .or_else(|| ri.get(idx));
+        match paired {
+            Some(r) => pairs.push((*l, *r)),
+            None => errors.push(Error::MissingInitialState(Side::Right, *l)),
+        }
+    }
+    for r in ri {
+        if !pairs.iter().any(|(_, p)| p == r) && !li.iter().any(|l| fits(*l, *r)) {
+            errors.push(Error::MissingInitialState(Side::Left, *r));
+        }
+    }
+    (pairs, errors)
+}
+
+/// Check that `right` refines `left`: it may omit commands and react to additional events, but
+/// must handle every event of `left` and must not offer commands that `left` does not offer.
+///
+/// Omitted commands are reported as [`Error::OmittedCommand`], see [`Error::is_unsafe`].
+///
+/// Initial states are paired like in [`correspondence`], where a pair fits if `right` is a safe
+/// refinement when starting from it. The visited pairs are returned for tracing the errors.
+pub fn refinement(
+    left: &Graph,
+    li: &[NodeId],
+    right: &Graph,
+    ri: &[NodeId],
+) -> (Vec<Error>, Parents) {
+    let _span = tracing::debug_span!("refinement").entered();
+
+    let (pairs, mut errors) = initial_pairs(left, li, right, ri, |l, r| {
+        !walk_refinement(left, right, &[(l, r)], &mut Parents::default())
+            .iter()
+            .any(Error::is_unsafe)
+    });
+    let mut parents = Parents::default();
+    errors.extend(walk_refinement(left, right, &pairs, &mut parents));
+    (errors, parents)
+}
+
+/// check the refinement for the states reachable from the given pairs of initial states
+fn walk_refinement(
+    left: &Graph,
+    right: &Graph,
+    pairs: &[(NodeId, NodeId)],
+    parents: &mut Parents,
+) -> Vec<Error> {
+    use Side::*;
+
+    let mut errors = Vec::new();
+    // unlike for equivalence a node may be related to several nodes on the other side
+    let mut stack = pairs.iter().copied().rev().collect::<Vec<_>>();
+    for pair in &stack {
+        parents.0.entry(*pair).or_insert(None);
+    }
+
+    while let Some((li, ri)) = stack.pop() {
+        tracing::debug!(left = %state_name(left, li), right = %state_name(right, ri), "loop");
+        let mut l_out = BTreeMap::new();
+        for edge in left.edges_directed(li, Outgoing) {
+            l_out
+                .entry(DeterministicLabel::from(edge.weight()))
+                .and_modify(|_| errors.push(Error::NonDeterministic(Left, edge.id())))
+                .or_insert(edge);
+        }
+        let mut r_out = BTreeMap::new();
+        for edge in right.edges_directed(ri, Outgoing) {
+            r_out
+                .entry(DeterministicLabel::from(edge.weight()))
+                .and_modify(|_| errors.push(Error::NonDeterministic(Right, edge.id())))
+                .or_insert(edge);
+        }
+        for label in l_out.keys().chain(r_out.keys()).collect::<BTreeSet<_>>() {
+            match (l_out.get(label), r_out.get(label)) {
+                (Some(l_edge), Some(r_edge)) if l_edge.weight() == r_edge.weight() => {
+                    let next = (l_edge.target(), r_edge.target());
+                    if let Entry::Vacant(entry) = parents.0.entry(next) {
+                        entry.insert(Some(((li, ri), l_edge.id())));
+                        stack.push(next);
+                    }
+                }
+                // same command with a different log
+                (Some(_), Some(r_edge)) => {
+                    errors.push(Error::MissingTransition(Left, li, r_edge.id()))
+                }
+                (Some(l_edge), None) => match l_edge.weight() {
+                    MachineLabel::Execute { .. } => {
+                        errors.push(Error::OmittedCommand(ri, l_edge.id()))
+                    }
+                    MachineLabel::Input { .. } => {
+                        errors.push(Error::MissingTransition(Right, ri, l_edge.id()))
+                    }
+                },
+                (None, Some(r_edge)) => match r_edge.weight() {
+                    MachineLabel::Execute { .. } => {
+                        errors.push(Error::MissingTransition(Left, li, r_edge.id()))
+                    }
+                    // such events never arrive in this state
+                    MachineLabel::Input { .. } => {}
+                },
+                (None, None) => unreachable!(),
+            }
+        }
+    }
+
     errors
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::types::{
+        CheckResult, Diagnostic, ErrorCode, EventType, MachineLabel, Role, State, TraceStep,
+    };
+    use itertools::Itertools;
     use pretty_assertions::assert_eq;
     use tracing_subscriber::{fmt, fmt::format::FmtSpan, EnvFilter};
 
@@ -325,6 +742,319 @@ mod tests {
             .ok();
     }
 
+    #[test]
+    fn trace() {
+        setup_logger();
+        let swarm = r#"{
+            "initial":"S0",
+            "transitions":[
+                {"source":"S0","target":"S1","label":{"role":"R","cmd":"a","logType":["A"]}},
+                {"source":"S1","target":"S2","label":{"role":"R","cmd":"b","logType":["B","C"]}},
+                {"source":"S2","target":"S3","label":{"role":"R","cmd":"c","logType":["D"]}}
+            ]}"#;
+        let subs = r#"{ "R":["A","B","C","D"] }"#;
+        let machine = r#"{
+            "initial":"S0",
+            "transitions":[
+                {"source":"S0","target":"S0","label":{"tag":"Execute","cmd":"a","logType":["A"]}},
+                {"source":"S0","target":"S1","label":{"tag":"Input","eventType":"A"}},
+                {"source":"S1","target":"S1","label":{"tag":"Execute","cmd":"b","logType":["B","C"]}},
+                {"source":"S1","target":"X","label":{"tag":"Input","eventType":"B"}},
+                {"source":"X","target":"S2","label":{"tag":"Input","eventType":"C"}},
+                {"source":"S2","target":"S3","label":{"tag":"Input","eventType":"D"}}
+            ]}"#;
+
+        let errors = crate::check_projection(
+            &serde_json::from_str(swarm).unwrap(),
+            &serde_json::from_str(subs).unwrap(),
+            Role::new("R"),
+            &serde_json::from_str(machine).unwrap(),
+        )
+        .unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].message,
+            "missing transition c/D in state S2 (from reference state S2)"
+        );
+        let input = |ev: &str| MachineLabel::Input {
+            event_type: EventType::new(ev),
+        };
+        let step = |ev: &str, reference: &str, specimen: &str| TraceStep {
+            label: input(ev),
+            reference: State::new(reference),
+            specimen: State::new(specimen),
+        };
+        assert_eq!(
+            errors[0].trace,
+            vec![
+                step("A", "S1", "S1"),
+                step("B", "S1(+1)", "X"),
+                step("C", "S2", "S2")
+            ]
+        );
+    }
+
+    #[test]
+    fn project_machine() {
+        setup_logger();
+        let swarm = serde_json::from_str(
+            r#"{
+            "initial":"S0",
+            "transitions":[
+                {"source":"S0","target":"S1","label":{"role":"R","cmd":"a","logType":["A"]}},
+                {"source":"S1","target":"S2","label":{"role":"R","cmd":"b","logType":["B","C"]}},
+                {"source":"S1","target":"S3","label":{"role":"S","cmd":"x","logType":["X","Y"]}}
+            ]}"#,
+        )
+        .unwrap();
+        let subs = serde_json::from_str(r#"{ "R":["A","B","C","X","Y"], "S":["X"] }"#).unwrap();
+        let machine = crate::project_machine(&swarm, &subs, Role::new("R")).unwrap();
+        assert_eq!(
+            serde_json::to_value(&machine).unwrap(),
+            serde_json::json!({
+                "initial": "S0",
+                "transitions": [
+                    {"source":"S0","target":"S0","label":{"tag":"Execute","cmd":"a","logType":["A"]}},
+                    {"source":"S1","target":"S1","label":{"tag":"Execute","cmd":"b","logType":["B","C"]}},
+                    {"source":"S0","target":"S1","label":{"tag":"Input","eventType":"A"}},
+                    {"source":"S1","target":"S1(+1)","label":{"tag":"Input","eventType":"B"}},
+                    {"source":"S1(+1)","target":"S2","label":{"tag":"Input","eventType":"C"}},
+                    {"source":"S1","target":"S1(+1) #2","label":{"tag":"Input","eventType":"X"}},
+                    {"source":"S1(+1) #2","target":"S3","label":{"tag":"Input","eventType":"Y"}}
+                ]
+            })
+        );
+        let mapping = crate::check_projection(&swarm, &subs, Role::new("R"), &machine).unwrap();
+        assert_eq!(
+            mapping
+                .iter()
+                .map(|(state, states)| format!("{state} -> {}", states.iter().join(", ")))
+                .collect::<Vec<_>>(),
+            vec![
+                "S0 -> S0",
+                "S1 -> S1",
+                "S1(+1) -> S1(+1)",
+                "S1(+1) #2 -> S1(+1) #2",
+                "S2 -> S2",
+                "S3 -> S3"
+            ]
+        );
+    }
+
+    #[test]
+    fn unreachable_source() {
+        // U is not reachable, but its transition leads into the reachable state S1
+        let proto = crate::text::parse_swarm(
+            "initial S0\n\
+             (S0) --[a@R<A>]--> (S1)\n\
+             (U) --[b@R<B>]--> (S1)\n",
+        )
+        .unwrap();
+        let subs = serde_json::from_str(r#"{ "R": ["A", "B"] }"#).unwrap();
+        let (graph, initials, _) = crate::swarm::check(proto, &subs);
+        let (machine, initials) = super::project(&graph, &initials.unwrap(), &subs, Role::new("R"));
+        assert_eq!(initials.len(), 1);
+        assert_eq!(
+            machine
+                .edge_indices()
+                .map(|e| {
+                    let (source, target) = machine.edge_endpoints(e).unwrap();
+                    let name = |n| super::state_name(&machine, n);
+                    format!(
+                        "({}) --[{}]--> ({})",
+                        name(source),
+                        machine[e],
+                        name(target)
+                    )
+                })
+                .collect::<Vec<_>>(),
+            vec!["(S0) --[a/A]--> (S0)", "(S0) --[A?]--> (S1)"]
+        );
+    }
+
+    #[test]
+    fn refinement() {
+        setup_logger();
+        let swarm = serde_json::from_str(
+            r#"{
+            "initial":"S0",
+            "transitions":[
+                {"source":"S0","target":"S1","label":{"role":"R","cmd":"a","logType":["A"]}},
+                {"source":"S1","target":"S2","label":{"role":"R","cmd":"b","logType":["B"]}},
+                {"source":"S1","target":"S3","label":{"role":"R","cmd":"c","logType":["C"]}}
+            ]}"#,
+        )
+        .unwrap();
+        let subs = serde_json::from_str(r#"{ "R":["A","B","C"] }"#).unwrap();
+        let messages = |diagnostics: Vec<Diagnostic>| {
+            diagnostics
+                .into_iter()
+                .map(|d| (d.code, d.message))
+                .collect::<Vec<_>>()
+        };
+
+        // does not offer c and handles an event that never arrives
+        let machine = serde_json::from_str(
+            r#"{
+            "initial":"S0",
+            "transitions":[
+                {"source":"S0","target":"S0","label":{"tag":"Execute","cmd":"a","logType":["A"]}},
+                {"source":"S0","target":"S1","label":{"tag":"Input","eventType":"A"}},
+                {"source":"S1","target":"S1","label":{"tag":"Execute","cmd":"b","logType":["B"]}},
+                {"source":"S1","target":"S2","label":{"tag":"Input","eventType":"B"}},
+                {"source":"S1","target":"S3","label":{"tag":"Input","eventType":"C"}},
+                {"source":"S2","target":"S0","label":{"tag":"Input","eventType":"X"}}
+            ]}"#,
+        )
+        .unwrap();
+        let result = crate::check_refinement(&swarm, &subs, Role::new("R"), &machine);
+        // the trace follows the pairs of states visited by the refinement check
+        assert_eq!(
+            result.as_ref().unwrap()[0].trace,
+            vec![TraceStep {
+                label: MachineLabel::Input {
+                    event_type: EventType::new("A")
+                },
+                reference: State::new("S1"),
+                specimen: State::new("S1"),
+            }]
+        );
+        assert_eq!(
+            result.map(messages),
+            Ok(vec![(
+                ErrorCode::CommandNotOffered,
+                "command c/C not offered in state S1 (from reference state S1)".to_owned()
+            )])
+        );
+        assert!(crate::check_projection(&swarm, &subs, Role::new("R"), &machine).is_err());
+
+        // offers a command that is not allowed and cannot handle C
+        let machine = serde_json::from_str(
+            r#"{
+            "initial":"S0",
+            "transitions":[
+                {"source":"S0","target":"S0","label":{"tag":"Execute","cmd":"a","logType":["A"]}},
+                {"source":"S0","target":"S0","label":{"tag":"Execute","cmd":"d","logType":["D"]}},
+                {"source":"S0","target":"S1","label":{"tag":"Input","eventType":"A"}},
+                {"source":"S1","target":"S1","label":{"tag":"Execute","cmd":"b","logType":["B"]}},
+                {"source":"S1","target":"S2","label":{"tag":"Input","eventType":"B"}}
+            ]}"#,
+        )
+        .unwrap();
+        let result = crate::check_refinement(&swarm, &subs, Role::new("R"), &machine);
+        assert_eq!(
+            result.map_err(messages),
+            Err(vec![
+                (
+                    ErrorCode::ExtraneousTransition,
+                    "extraneous transition d/D in state S0".to_owned()
+                ),
+                (
+                    ErrorCode::CommandNotOffered,
+                    "command c/C not offered in state S1 (from reference state S1)".to_owned()
+                ),
+                (
+                    ErrorCode::MissingTransition,
+                    "missing transition C? in state S1 (from reference state S1)".to_owned()
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn multiple_initial() {
+        setup_logger();
+        let swarm = serde_json::from_str(
+            r#"{
+            "initial":["Closed","Open"],
+            "transitions":[
+                {"source":"Closed","target":"Open","label":{"role":"R","cmd":"open","logType":["opened"]}},
+                {"source":"Open","target":"Closed","label":{"role":"R","cmd":"close","logType":["closed"]}}
+            ]}"#,
+        )
+        .unwrap();
+        let subs = serde_json::from_str(r#"{ "R":["opened","closed"] }"#).unwrap();
+        let proj = crate::project_machine(&swarm, &subs, Role::new("R")).unwrap();
+        assert_eq!(proj.initial, vec![State::new("Closed"), State::new("Open")]);
+
+        let machine = |initial: &str| {
+            serde_json::from_str(&format!(
+                r#"{{
+                "initial":{initial},
+                "transitions":[
+                    {{"source":"Shut","target":"Shut","label":{{"tag":"Execute","cmd":"open","logType":["opened"]}}}},
+                    {{"source":"Shut","target":"Ajar","label":{{"tag":"Input","eventType":"opened"}}}},
+                    {{"source":"Ajar","target":"Ajar","label":{{"tag":"Execute","cmd":"close","logType":["closed"]}}}},
+                    {{"source":"Ajar","target":"Shut","label":{{"tag":"Input","eventType":"closed"}}}}
+                ]}}"#
+            ))
+            .unwrap()
+        };
+        let mapping = crate::check_projection(
+            &swarm,
+            &subs,
+            Role::new("R"),
+            &machine(r#"["Shut","Ajar"]"#),
+        )
+        .unwrap();
+        assert_eq!(
+            mapping
+                .iter()
+                .map(|(state, states)| format!("{state} -> {}", states.iter().join(", ")))
+                .collect::<Vec<_>>(),
+            vec!["Closed -> Shut", "Open -> Ajar"]
+        );
+        // initial states form a set
+        assert!(crate::check_projection(
+            &swarm,
+            &subs,
+            Role::new("R"),
+            &machine(r#"["Ajar","Shut"]"#)
+        )
+        .is_ok());
+
+        let errors = crate::check_projection(&swarm, &subs, Role::new("R"), &machine(r#""Shut""#))
+            .unwrap_err();
+        assert_eq!(
+            errors
+                .into_iter()
+                .map(|d| (d.code, d.message))
+                .collect::<Vec<_>>(),
+            vec![(
+                ErrorCode::InitialStateMismatch,
+                "missing initial state corresponding to reference state Open".to_owned()
+            )]
+        );
+    }
+
+    #[test]
+    fn equivalent_initial_states() {
+        setup_logger();
+        let swarm = serde_json::from_str(
+            r#"{
+            "initial":["Closed","Open"],
+            "transitions":[
+                {"source":"Closed","target":"Open","label":{"role":"R","cmd":"open","logType":["opened","tick"]}},
+                {"source":"Open","target":"Closed","label":{"role":"R","cmd":"close","logType":["closed","tick"]}}
+            ]}"#,
+        )
+        .unwrap();
+        // the observer only sees the ticks and cannot tell the initial states apart
+        let subs =
+            serde_json::from_str(r#"{ "R":["opened","closed","tick"], "O":["tick"] }"#).unwrap();
+        let proj = crate::project_machine(&swarm, &subs, Role::new("O")).unwrap();
+        let minimised = crate::minimise_machine(&proj).unwrap().protocol;
+        assert_eq!(minimised.initial, vec![State::new("Closed")]);
+        assert_eq!(
+            crate::check_projection(&swarm, &subs, Role::new("O"), &minimised).map(|_| ()),
+            Ok(())
+        );
+        assert_eq!(
+            crate::check_refinement(&swarm, &subs, Role::new("O"), &minimised),
+            Ok(vec![])
+        );
+    }
+
     #[test]
     fn paper() {
         setup_logger();
@@ -371,14 +1101,20 @@ mod tests {
             ]}"#;
 
         let result = crate::check_projection(
-            swarm.to_owned(),
-            subs.to_owned(),
-            "P".to_owned(),
-            machine.to_owned(),
+            &serde_json::from_str(swarm).unwrap(),
+            &serde_json::from_str(subs).unwrap(),
+            Role::new("P"),
+            &serde_json::from_str(machine).unwrap(),
+        );
+        let mut expected = Diagnostic::new(
+            ErrorCode::GuardNotInvariant,
+            "guard event type Bid appears in transitions from multiple states".to_owned(),
         );
+        expected.event_types = vec![EventType::new("Bid")];
+        assert_eq!(result, Err(vec![expected.clone()]));
         assert_eq!(
-            result,
-            r#"{"type":"ERROR","errors":["guard event type Bid appears in transitions from multiple states"]}"#
+            serde_json::to_string(&CheckResult::from_diagnostics(vec![expected])).unwrap(),
+            r#"{"type":"ERROR","errors":["guard event type Bid appears in transitions from multiple states"],"diagnostics":[{"code":"GuardNotInvariant","message":"guard event type Bid appears in transitions from multiple states","eventTypes":["Bid"]}]}"#
         );
     }
 }
@@ -0,0 +1,481 @@
+//! Command-line interface for checking protocol files, e.g. in CI
+
+use clap::{Parser, Subcommand, ValueEnum};
+use machine_check::{
+    export,
+    simulation::SimulationOptions,
+    swarm, text,
+    types::{CheckResult, DataResult, Diagnostic, ErrorCode, Role},
+    Machine, Subscriptions, SwarmProtocol,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about = "Behavioural checks for swarm protocols and their machines"
+)]
+struct Cli {
+    /// output format
+    #[arg(long, value_enum, default_value_t = Format::Text, global = true)]
+    format: Format,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Json,
+    Text,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// check that a swarm protocol is well-formed
+    CheckSwarm {
+        /// swarm protocol in JSON or textual notation
+        protocol: PathBuf,
+        /// subscriptions in JSON
+        subscriptions: PathBuf,
+    },
+    /// check that a machine implements its role in a swarm protocol
+    CheckProjection {
+        protocol: PathBuf,
+        subscriptions: PathBuf,
+        role: String,
+        /// machine in JSON
+        machine: PathBuf,
+    },
+    /// print the machine that a role should implement
+    Project {
+        protocol: PathBuf,
+        subscriptions: PathBuf,
+        role: String,
+    },
+    /// render a swarm protocol or one of its projections as a diagram
+    Export {
+        protocol: PathBuf,
+        /// diagram format
+        #[arg(long, value_enum, default_value_t = Diagram::Dot)]
+        to: Diagram,
+        /// subscriptions in JSON, needed for highlighting errors and for projections
+        #[arg(long)]
+        subscriptions: Option<PathBuf>,
+        /// export the projection onto this role instead of the swarm protocol
+        #[arg(long, requires = "subscriptions")]
+        role: Option<String>,
+    },
+    /// print the JSON trace of a seeded random execution
+    Simulate {
+        protocol: PathBuf,
+        subscriptions: PathBuf,
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// number of commands after which the simulation stops
+        #[arg(long, default_value_t = 20)]
+        steps: usize,
+        /// largest number of ticks an event may take to reach a subscriber
+        #[arg(long, default_value_t = 0)]
+        max_delay: u64,
+        /// let events overtake each other on the way to a role
+        #[arg(long)]
+        reorder: bool,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Diagram {
+    Dot,
+    Mermaid,
+    Plantuml,
+    /// the textual notation, also used in error messages
+    Text,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let mut out = String::new();
+    let code = run(cli, &mut out);
+    print!("{out}");
+    code
+}
+
+/// Outcome of a command: success, failed check, or unusable input
+fn run(cli: Cli, out: &mut String) -> ExitCode {
+    match execute(cli.command, cli.format, out) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(diagnostics) => {
+            print_diagnostics(cli.format, diagnostics, out);
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// returns whether the check passed, or the diagnostics for input that could not be processed
+fn execute(command: Command, format: Format, out: &mut String) -> Result<bool, Vec<Diagnostic>> {
+    match command {
+        Command::CheckSwarm {
+            protocol,
+            subscriptions,
+        } => {
+            let proto = read_protocol(&protocol)?;
+            let subs = read_json::<Subscriptions>(&subscriptions, "subscriptions")?;
+            let (graph, _, errors) = swarm::check(proto, &subs);
+            let diagnostics = errors
+                .into_iter()
+                .map(swarm::Error::diagnostic(&graph))
+                .collect::<Vec<_>>();
+            let ok = diagnostics.is_empty();
+            print_diagnostics(format, diagnostics, out);
+            Ok(ok)
+        }
+        Command::CheckProjection {
+            protocol,
+            subscriptions,
+            role,
+            machine,
+        } => {
+            let proto = read_protocol(&protocol)?;
+            let subs = read_json::<Subscriptions>(&subscriptions, "subscriptions")?;
+            let machine = read_json::<Machine>(&machine, "machine")?;
+            let diagnostics =
+                machine_check::check_projection(&proto, &subs, Role::new(&role), &machine)
+                    .err()
+                    .unwrap_or_default();
+            let ok = diagnostics.is_empty();
+            print_diagnostics(format, diagnostics, out);
+            Ok(ok)
+        }
+        Command::Project {
+            protocol,
+            subscriptions,
+            role,
+        } => {
+            let proto = read_protocol(&protocol)?;
+            let subs = read_json::<Subscriptions>(&subscriptions, "subscriptions")?;
+            let machine = machine_check::project_machine(&proto, &subs, Role::new(&role))?;
+            match format {
+                Format::Json => print_json(&DataResult::<_>::OK { data: machine }, out),
+                Format::Text => out.push_str(&machine_text(&machine)),
+            }
+            Ok(true)
+        }
+        Command::Export {
+            protocol,
+            to,
+            subscriptions,
+            role,
+        } => {
+            let proto = read_protocol(&protocol)?;
+            let subs = subscriptions
+                .map(|s| read_json::<Subscriptions>(&s, "subscriptions"))
+                .transpose()?;
+            let diagram = match (role, subs) {
+                (Some(role), Some(subs)) => {
+                    let role = Role::new(&role);
+                    match to {
+                        Diagram::Dot => machine_check::projection_dot(&proto, &subs, role)?,
+                        Diagram::Mermaid => export::machine_to_mermaid(
+                            &machine_check::project_machine(&proto, &subs, role)?,
+                        ),
+                        Diagram::Plantuml => export::machine_to_plantuml(
+                            &machine_check::project_machine(&proto, &subs, role)?,
+                        ),
+                        Diagram::Text => {
+                            machine_text(&machine_check::project_machine(&proto, &subs, role)?)
+                        }
+                    }
+                }
+                (_, subs) => match to {
+                    Diagram::Dot => match subs {
+                        Some(subs) => machine_check::swarm_dot(&proto, &subs),
+                        None => {
+                            let (graph, initials, _) = swarm::check(proto, &Subscriptions::new());
+                            export::swarm_to_dot(&graph, &initials.unwrap_or_default(), &[])
+                        }
+                    },
+                    Diagram::Mermaid => export::swarm_to_mermaid(&proto),
+                    Diagram::Plantuml => export::swarm_to_plantuml(&proto),
+                    Diagram::Text => text::print_swarm(&proto),
+                },
+            };
+            match format {
+                Format::Json => print_json(&DataResult::<_>::OK { data: diagram }, out),
+                Format::Text => out.push_str(&diagram),
+            }
+            Ok(true)
+        }
+        Command::Simulate {
+            protocol,
+            subscriptions,
+            seed,
+            steps,
+            max_delay,
+            reorder,
+        } => {
+            let proto = read_protocol(&protocol)?;
+            let subs = read_json::<Subscriptions>(&subscriptions, "subscriptions")?;
+            let options = SimulationOptions {
+                seed,
+                max_steps: steps,
+                max_delay,
+                reorder,
+            };
+            let trace = machine_check::simulate(&proto, &subs, &options)?;
+            // the trace is JSON in any case, so that it can be used as a fixture
+            match format {
+                Format::Json => print_json(&DataResult::<_>::OK { data: trace }, out),
+                Format::Text => print_json(&trace, out),
+            }
+            Ok(true)
+        }
+    }
+}
+
+/// print a machine in the same notation as swarm protocols, using the machine label format
+fn machine_text(machine: &Machine) -> String {
+    let mut out = format!("initial {}\n", machine.initial.join(", "));
+    if !machine.final_states.is_empty() {
+        out.push_str(&format!("final {}\n", machine.final_states.join(", ")));
+    }
+    for t in &machine.transitions {
+        out.push_str(&format!(
+            "({}) --[{}]--> ({})\n",
+            t.source, t.label, t.target
+        ));
+    }
+    out
+}
+
+/// read a swarm protocol in JSON or, if the file does not start with `{`, in textual notation
+fn read_protocol(path: &Path) -> Result<SwarmProtocol, Vec<Diagnostic>> {
+    let input = read(path)?;
+    if input.trim_start().starts_with('{') {
+        parse_json(&input, path, "swarm protocol")
+    } else {
+        text::parse_swarm(&input)
+            .map_err(|e| parse_error(format!("parsing swarm protocol {}: {}", path.display(), e)))
+    }
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path, what: &str) -> Result<T, Vec<Diagnostic>> {
+    parse_json(&read(path)?, path, what)
+}
+
+fn parse_json<T: DeserializeOwned>(
+    input: &str,
+    path: &Path,
+    what: &str,
+) -> Result<T, Vec<Diagnostic>> {
+    serde_json::from_str(input)
+        .map_err(|e| parse_error(format!("parsing {what} {}: {}", path.display(), e)))
+}
+
+fn read(path: &Path) -> Result<String, Vec<Diagnostic>> {
+    fs::read_to_string(path).map_err(|e| parse_error(format!("reading {}: {}", path.display(), e)))
+}
+
+fn parse_error(message: String) -> Vec<Diagnostic> {
+    vec![Diagnostic::new(ErrorCode::ParseError, message)]
+}
+
+fn print_diagnostics(format: Format, diagnostics: Vec<Diagnostic>, out: &mut String) {
+    match format {
+        Format::Json => print_json(&CheckResult::from_diagnostics(diagnostics), out),
+        Format::Text if diagnostics.is_empty() => out.push_str("OK\n"),
+        Format::Text => {
+            for d in diagnostics {
+                let _ = writeln!(out, "error: {}", d.message);
+                if !d.trace.is_empty() {
+                    let trace = d.trace.iter().map(|step| step.label.to_string());
+                    let _ = writeln!(
+                        out,
+                        "  reached via: {}",
+                        trace.collect::<Vec<_>>().join(", ")
+                    );
+                }
+                for fix in d.fixes {
+                    let _ = writeln!(out, "  possible fix: {fix}");
+                }
+            }
+        }
+    }
+}
+
+fn print_json(value: &impl Serialize, out: &mut String) {
+    out.push_str(&serde_json::to_string_pretty(value).unwrap());
+    out.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::iter::once;
+
+    /// directory holding the input files of one test, removed again when the test ends
+    struct Inputs(PathBuf);
+
+    impl Inputs {
+        fn new(test: &str) -> Self {
+            let dir = std::env::temp_dir()
+                .join(format!("machine-check-cli-{test}-{}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn file(&self, name: &str, content: &str) -> String {
+            let path = self.0.join(name);
+            fs::write(&path, content).unwrap();
+            path.to_str().unwrap().to_owned()
+        }
+    }
+
+    impl Drop for Inputs {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn cli(args: &[&str]) -> (ExitCode, String) {
+        let cli = Cli::try_parse_from(once("machine-check").chain(args.iter().copied())).unwrap();
+        let mut out = String::new();
+        let code = run(cli, &mut out);
+        (code, out)
+    }
+
+    const PROTO: &str = "initial S0\n(S0) --[a@R1<A>]--> (S1)\n(S1) --[b@R2<B>]--> (S2)\n";
+
+    #[test]
+    fn check_swarm() {
+        let inputs = Inputs::new("check_swarm");
+        let proto = &inputs.file("proto.txt", PROTO);
+        let good = &inputs.file("good.json", r#"{ "R1": ["A"], "R2": ["A", "B"] }"#);
+        let bad = &inputs.file("bad.json", r#"{ "R1": ["A"], "R2": ["B"] }"#);
+
+        assert_eq!(
+            cli(&["check-swarm", proto, good]),
+            (ExitCode::SUCCESS, "OK\n".to_owned())
+        );
+        assert_eq!(
+            cli(&["check-swarm", proto, bad]),
+            (
+                ExitCode::from(1),
+                "error: subsequently active role R2 does not subscribe to events in transition (S0)--[a@R1<A>]-->(S1)\n  \
+                 possible fix: add event type `A` to role `R2`\n\
+                 error: subsequently involved role R2 does not subscribe to guard in transition (S0)--[a@R1<A>]-->(S1)\n  \
+                 possible fix: add event type `A` to role `R2`\n"
+                    .to_owned()
+            )
+        );
+
+        let (code, out) = cli(&["check-swarm", "--format", "json", proto, "/does/not/exist"]);
+        assert_eq!(code, ExitCode::from(2));
+        let out = serde_json::from_str::<serde_json::Value>(&out).unwrap();
+        assert_eq!(out["diagnostics"][0]["code"], "ParseError");
+    }
+
+    #[test]
+    fn check_projection() {
+        let inputs = Inputs::new("check_projection");
+        let proto = &inputs.file("proto.txt", PROTO);
+        let subs = &inputs.file("subs.json", r#"{ "R1": ["A"], "R2": ["A", "B"] }"#);
+        let good = &inputs.file(
+            "good.json",
+            r#"{
+                "initial": "S0",
+                "transitions": [
+                    { "label": { "tag": "Execute", "cmd": "a", "logType": ["A"] }, "source": "S0", "target": "S0" },
+                    { "label": { "tag": "Input", "eventType": "A" }, "source": "S0", "target": "S1" }
+                ]
+            }"#,
+        );
+        let bad = &inputs.file(
+            "bad.json",
+            r#"{
+                "initial": "S0",
+                "transitions": [
+                    { "label": { "tag": "Execute", "cmd": "a", "logType": ["A"] }, "source": "S0", "target": "S0" }
+                ]
+            }"#,
+        );
+
+        assert_eq!(
+            cli(&["check-projection", proto, subs, "R1", good]),
+            (ExitCode::SUCCESS, "OK\n".to_owned())
+        );
+        assert_eq!(
+            cli(&["check-projection", proto, subs, "R1", bad]),
+            (
+                ExitCode::from(1),
+                "error: missing transition A? in state S0 (from reference state S0)\n".to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn project() {
+        let inputs = Inputs::new("project");
+        let proto = &inputs.file("proto.txt", "initial S0\n(S0) --[a@R<A,B>]--> (S1)\n");
+        let subs = &inputs.file("subs.json", r#"{ "R": ["A", "B"] }"#);
+        assert_eq!(
+            cli(&["project", proto, subs, "R"]),
+            (
+                ExitCode::SUCCESS,
+                "initial S0\n\
+                 (S0) --[a/A,B]--> (S0)\n\
+                 (S0) --[A?]--> (S0(+1))\n\
+                 (S0(+1)) --[B?]--> (S1)\n"
+                    .to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn export() {
+        let inputs = Inputs::new("export");
+        let proto = &inputs.file("proto.txt", PROTO);
+        let subs = &inputs.file("subs.json", r#"{ "R1": ["A"], "R2": ["A", "B"] }"#);
+        assert_eq!(
+            cli(&["export", proto, "--to", "mermaid"]),
+            (
+                ExitCode::SUCCESS,
+                "stateDiagram-v2\n    \
+                 state \"S0\" as s0\n    \
+                 state \"S1\" as s1\n    \
+                 state \"S2\" as s2\n    \
+                 [*] --> s0\n    \
+                 s0 --> s1 : a@R1#lt;A#gt;\n    \
+                 s1 --> s2 : b@R2#lt;B#gt;\n"
+                    .to_owned()
+            )
+        );
+        assert_eq!(
+            cli(&[
+                "export",
+                proto,
+                "--to",
+                "text",
+                "--subscriptions",
+                subs,
+                "--role",
+                "R2"
+            ]),
+            (
+                ExitCode::SUCCESS,
+                "initial S0\n\
+                 (S1) --[b/B]--> (S1)\n\
+                 (S0) --[A?]--> (S1)\n\
+                 (S1) --[B?]--> (S2)\n"
+                    .to_owned()
+            )
+        );
+    }
+}
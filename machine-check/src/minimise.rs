@@ -0,0 +1,315 @@
+//! Merging behaviourally equivalent states of swarm protocols and machines
+//!
+//! Two states are equivalent if they are bisimilar, i.e. for every transition from one state there
+//! is a transition with the same label from the other state such that the targets are equivalent
+//! again; final states are never merged with non-final ones. Initial states keep their name (the
+//! first one listed if several of them are merged), all other states of the minimised graph are
+//! named after the member of their equivalence class that appears first in the input.
+
+use crate::{
+    machine,
+    types::{MachineLabel, Protocol, State, SwarmLabel},
+    NodeId,
+};
+use itertools::Itertools;
+use petgraph::{
+    visit::{Bfs, EdgeRef, VisitMap, Visitable, Walker},
+    Direction::Outgoing,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
+
+/// A minimised protocol together with the state each original state was merged into
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Minimised<L> {
+    pub protocol: Protocol<L>,
+    /// maps each reachable state of the input to its state in `protocol`
+    pub mapping: BTreeMap<State, State>,
+}
+
+/// Compute the coarsest bisimulation on the states reachable from the initial states that does not
+/// relate final and non-final states.
+///
+/// Returns the equivalence class for each node (`None` for unreachable nodes); classes are
+/// numbered in the breadth-first order of their first member.
+pub fn partition<N, L: Ord>(
+    graph: &petgraph::Graph<N, L>,
+    initials: &[NodeId],
+    finals: &[NodeId],
+) -> Vec<Option<usize>> {
+    let mut bfs = Bfs {
+        stack: VecDeque::new(),
+        discovered: graph.visit_map(),
+    };
+    for initial in initials {
+        if bfs.discovered.visit(*initial) {
+            bfs.stack.push_back(*initial);
+        }
+    }
+    let order = bfs.iter(graph).collect::<Vec<_>>();
+    let mut class = vec![None; graph.node_count()];
+    for node in &order {
+        class[node.index()] = Some(usize::from(finals.contains(node)));
+    }
+    let mut count = order.iter().map(|n| class[n.index()]).unique().count();
+    loop {
+        // split each class according to the labels and target classes of the outgoing edges
+        let mut signatures = BTreeMap::new();
+        let mut next = vec![None; graph.node_count()];
+        for node in &order {
+            let signature = graph
+                .edges_directed(*node, Outgoing)
+                .map(|e| (e.weight(), class[e.target().index()]))
+                .collect::<BTreeSet<_>>();
+            let len = signatures.len();
+            let c = *signatures
+                .entry((class[node.index()], signature))
+                .or_insert(len);
+            next[node.index()] = Some(c);
+        }
+        class = next;
+        if signatures.len() == count {
+            return class;
+        }
+        count = signatures.len();
+    }
+}
+
+/// Build the graph with one state per equivalence class, using `representative` to pick the
+/// state whose weight is kept from the members of a class (given in the order of their indices)
+/// unless the class contains an initial state.
+///
+/// Returns the minimised graph, the new node for each initial state, and the new node for each
+/// original node.
+pub fn quotient<N: Clone, L: Ord + Clone>(
+    graph: &petgraph::Graph<N, L>,
+    initials: &[NodeId],
+    finals: &[NodeId],
+    representative: impl Fn(&[NodeId]) -> NodeId,
+) -> (petgraph::Graph<N, L>, Vec<NodeId>, Vec<Option<NodeId>>) {
+    let class = partition(graph, initials, finals);
+    let count = class.iter().flatten().max().map_or(0, |c| c + 1);
+    let mut members = vec![Vec::new(); count];
+    for node in graph.node_indices() {
+        if let Some(c) = class[node.index()] {
+            members[c].push(node);
+        }
+    }
+
+    let mut result = petgraph::Graph::new();
+    let reps = members
+        .iter()
+        .map(|m| {
+            let rep = match initials.iter().find(|i| m.contains(i)) {
+                Some(initial) => *initial,
+                None => representative(m),
+            };
+            result.add_node(graph[rep].clone());
+            rep
+        })
+        .collect::<HashSet<_>>();
+    let mapping = class.iter().map(|c| c.map(NodeId::new)).collect::<Vec<_>>();
+    let mut added = BTreeSet::new();
+    for edge in graph.edge_references() {
+        if !reps.contains(&edge.source()) {
+            continue;
+        }
+        let source = mapping[edge.source().index()].unwrap();
+        let target = mapping[edge.target().index()].unwrap();
+        if added.insert((source, edge.weight(), target)) {
+            result.add_edge(source, target, edge.weight().clone());
+        }
+    }
+    let initials = initials
+        .iter()
+        .map(|i| mapping[i.index()].unwrap())
+        .collect();
+    (result, initials, mapping)
+}
+
+/// Merge equivalent states of a swarm protocol.
+pub fn swarm(
+    graph: &crate::Graph,
+    initials: &[NodeId],
+    finals: &[NodeId],
+) -> (crate::Graph, Vec<NodeId>, Vec<Option<NodeId>>) {
+    let _span = tracing::debug_span!("minimise swarm").entered();
+    quotient(graph, initials, finals, |m| m[0])
+}
+
+/// Merge equivalent states of a machine, preferring named states over the synthetic ones
+/// created by the projection.
+pub fn machine(
+    graph: &machine::Graph,
+    initials: &[NodeId],
+    finals: &[NodeId],
+) -> (machine::Graph, Vec<NodeId>, Vec<Option<NodeId>>) {
+    let _span = tracing::debug_span!("minimise machine").entered();
+    quotient(graph, initials, finals, |m| {
+        m.iter()
+            .copied()
+            .find(|n| graph[*n].is_some())
+            .unwrap_or(m[0])
+    })
+}
+
+/// Merge equivalent states of a swarm protocol and convert the result to JSON.
+pub fn minimise_swarm(
+    graph: &crate::Graph,
+    initials: &[NodeId],
+    finals: &[NodeId],
+) -> Minimised<SwarmLabel> {
+    let (min, min_initials, mapping) = swarm(graph, initials, finals);
+    let mut protocol = crate::swarm::to_json(&min, &min_initials);
+    protocol.final_states = final_names(finals, &mapping, |n| min[n].clone());
+    Minimised {
+        protocol,
+        mapping: graph
+            .node_indices()
+            .filter_map(|n| Some((graph[n].clone(), min[mapping[n.index()]?].clone())))
+            .collect(),
+    }
+}
+
+/// Merge equivalent states of a machine and convert the result to JSON.
+pub fn minimise_machine(
+    graph: &machine::Graph,
+    initials: &[NodeId],
+    finals: &[NodeId],
+) -> Minimised<MachineLabel> {
+    let (min, min_initials, mapping) = machine(graph, initials, finals);
+    let name = |g: &machine::Graph, n: NodeId| State::new(&machine::state_name(g, n).to_string());
+    let mut protocol = machine::to_json(&min, &min_initials);
+    protocol.final_states = final_names(finals, &mapping, |n| name(&min, n));
+    Minimised {
+        protocol,
+        mapping: graph
+            .node_indices()
+            .filter_map(|n| Some((name(graph, n), name(&min, mapping[n.index()]?))))
+            .collect(),
+    }
+}
+
+/// names of the states the final states were merged into
+fn final_names(
+    finals: &[NodeId],
+    mapping: &[Option<NodeId>],
+    name: impl Fn(NodeId) -> State,
+) -> Vec<State> {
+    finals
+        .iter()
+        .filter_map(|n| mapping[n.index()])
+        .unique()
+        .map(name)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        text::parse_swarm,
+        types::{ErrorCode, State},
+    };
+    use itertools::Itertools;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn swarm() {
+        let proto = parse_swarm(
+            "initial S0\n\
+             (S0) --[a@R<A>]--> (S1)\n\
+             (S1) --[b@R<B>]--> (S2)\n\
+             (S2) --[a@R<A>]--> (S3)\n\
+             (S3) --[b@R<B>]--> (S0)\n\
+             (S1) --[c@Q<C>]--> (S4)\n\
+             (S3) --[c@Q<C>]--> (S5)\n",
+        )
+        .unwrap();
+        let result = crate::minimise_swarm(&proto).unwrap();
+        assert_eq!(
+            result.protocol,
+            parse_swarm(
+                "initial S0\n\
+                 (S0) --[a@R<A>]--> (S1)\n\
+                 (S1) --[b@R<B>]--> (S0)\n\
+                 (S1) --[c@Q<C>]--> (S4)\n",
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            result
+                .mapping
+                .iter()
+                .map(|(from, to)| format!("{from}->{to}"))
+                .collect::<Vec<_>>(),
+            vec!["S0->S0", "S1->S1", "S2->S0", "S3->S1", "S4->S4", "S5->S4"]
+        );
+    }
+
+    #[test]
+    fn projection() {
+        let swarm = parse_swarm(
+            "initial S0\n\
+             (S0) --[a@R<A>]--> (S1)\n\
+             (S1) --[b@R<B>]--> (S0)\n",
+        )
+        .unwrap();
+        let subs = serde_json::from_str(r#"{ "R": ["A", "B"] }"#).unwrap();
+        // the machine distinguishes even and odd rounds
+        let machine = serde_json::from_str::<crate::Machine>(
+            r#"{
+                "initial": "Even",
+                "transitions": [
+                    {"source":"Even","target":"Even","label":{"tag":"Execute","cmd":"a","logType":["A"]}},
+                    {"source":"Even","target":"EvenA","label":{"tag":"Input","eventType":"A"}},
+                    {"source":"EvenA","target":"EvenA","label":{"tag":"Execute","cmd":"b","logType":["B"]}},
+                    {"source":"EvenA","target":"Odd","label":{"tag":"Input","eventType":"B"}},
+                    {"source":"Odd","target":"Odd","label":{"tag":"Execute","cmd":"a","logType":["A"]}},
+                    {"source":"Odd","target":"OddA","label":{"tag":"Input","eventType":"A"}},
+                    {"source":"OddA","target":"OddA","label":{"tag":"Execute","cmd":"b","logType":["B"]}},
+                    {"source":"OddA","target":"Even","label":{"tag":"Input","eventType":"B"}}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        // skipping a round leads to an equivalent state, but the machine cannot choose
+        let mut skipping = machine.clone();
+        let mut skip = skipping.transitions[1].clone();
+        skip.target = State::new("OddA");
+        skipping.transitions.push(skip);
+        let errors = crate::check_projection(&swarm, &subs, "R".into(), &skipping).unwrap_err();
+        assert_eq!(
+            errors
+                .into_iter()
+                .map(|d| (d.code, d.message))
+                .collect::<Vec<_>>(),
+            vec![(
+                ErrorCode::NonDeterministicTransition,
+                "non-deterministic transition A? in state Even of the specimen".to_owned()
+            )]
+        );
+
+        let mapping = crate::check_projection(&swarm, &subs, "R".into(), &machine).unwrap();
+        assert_eq!(
+            mapping
+                .iter()
+                .map(|(state, states)| format!("{state} -> {}", states.iter().join(", ")))
+                .collect::<Vec<_>>(),
+            vec!["S0 -> Even, Odd", "S1 -> EvenA, OddA"]
+        );
+
+        let result = crate::minimise_machine(&machine).unwrap();
+        assert_eq!(result.protocol.transitions.len(), 4);
+
+        assert_eq!(
+            result
+                .mapping
+                .iter()
+                .map(|(from, to)| format!("{from}->{to}"))
+                .collect::<Vec<_>>(),
+            vec!["Even->Even", "EvenA->EvenA", "Odd->Even", "OddA->EvenA"]
+        );
+    }
+}
@@ -0,0 +1,370 @@
+//! Replaying recorded event logs against a swarm protocol
+
+use crate::{
+    machine::{self, state_name},
+    swarm::Edge,
+    types::{
+        Command, Diagnostic, DiagnosticLabel, ErrorCode, EventType, MachineLabel, Role, State,
+        SwarmLabel, Transition,
+    },
+    EdgeId, Graph, NodeId,
+};
+use petgraph::{visit::EdgeRef, Direction::Outgoing};
+use serde::{Deserialize, Serialize};
+
+/// Where the replay of a log ended
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Replay {
+    /// the state reached by the last complete transition
+    pub state: State,
+    /// the transition whose log has only partially been seen at the end of the replay
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending: Option<Transition<SwarmLabel>>,
+}
+
+/// Step through the swarm protocol along the given log of event types and the roles that
+/// emitted them.
+///
+/// The events of each transition must appear consecutively and in order. The first entry that
+/// does not fit is reported as [`ErrorCode::UnexpectedEvent`] together with the state reached
+/// and the transitions that would have been valid instead; the replay stops there.
+pub fn replay(
+    graph: &Graph,
+    initial: NodeId,
+    log: &[(EventType, Role)],
+) -> Result<Replay, Vec<Diagnostic>> {
+    let mut state = initial;
+    // transition in progress and number of its events seen so far
+    let mut pending = None::<(EdgeId, usize)>;
+    for (position, (event_type, role)) in log.iter().enumerate() {
+        let fits = |edge: EdgeId, idx: usize| {
+            let label = &graph[edge];
+            label.role == *role && label.log_type.get(idx) == Some(event_type)
+        };
+        let (edge, idx) = match pending {
+            Some((edge, idx)) if fits(edge, idx) => (edge, idx),
+            Some((edge, _)) => {
+                let message = format!(
+                    "event type {event_type} from role {role} at log position {position} does not continue transition {}",
+                    Edge(graph, edge)
+                );
+                return Err(vec![unexpected(
+                    graph,
+                    state,
+                    position,
+                    message,
+                    vec![edge],
+                )]);
+            }
+            None => {
+                let outgoing = graph.edges_directed(state, Outgoing).map(|e| e.id());
+                match outgoing.clone().find(|e| fits(*e, 0)) {
+                    Some(edge) => (edge, 0),
+                    None => {
+                        let message = format!(
+                            "event type {event_type} from role {role} at log position {position} is not allowed in state {}",
+                            graph[state]
+                        );
+                        let expected = outgoing.collect();
+                        return Err(vec![unexpected(graph, state, position, message, expected)]);
+                    }
+                }
+            }
+        };
+        tracing::debug!(position, %event_type, %role, ?edge, "replayed");
+        if idx + 1 == graph[edge].log_type.len() {
+            state = graph.edge_endpoints(edge).unwrap().1;
+            pending = None;
+        } else {
+            pending = Some((edge, idx + 1));
+        }
+    }
+    Ok(Replay {
+        state: graph[state].clone(),
+        pending: pending.map(|(edge, _)| {
+            let (source, target) = graph.edge_endpoints(edge).unwrap();
+            Transition {
+                label: graph[edge].clone(),
+                source: graph[source].clone(),
+                target: graph[target].clone(),
+            }
+        }),
+    })
+}
+
+/// Something a machine did or received, as recorded by the application
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "tag")]
+pub enum Observation {
+    Execute {
+        cmd: Command,
+    },
+    #[serde(rename_all = "camelCase")]
+    Input {
+        event_type: EventType,
+    },
+}
+
+impl std::fmt::Display for Observation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Observation::Execute { cmd } => write!(f, "command {cmd}"),
+            Observation::Input { event_type } => write!(f, "event type {event_type}"),
+        }
+    }
+}
+
+/// Result of feeding a machine’s history into its projection
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct MachineReplay {
+    /// the initial state followed by the state reached after each entry of the history
+    pub states: Vec<State>,
+    /// commands and inputs that were not possible in the state reached
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Step through a projected machine along the commands it executed and the events it received.
+///
+/// Like a running machine, the replay ignores events for which the current state has no input
+/// transition; these are reported as [`ErrorCode::UnexpectedEvent`]. Commands that are not
+/// enabled are reported as [`ErrorCode::CommandNotEnabled`].
+pub fn replay_machine(
+    machine: &machine::Graph,
+    initial: NodeId,
+    history: &[Observation],
+) -> MachineReplay {
+    let mut state = initial;
+    let mut states = vec![State::new(&state_name(machine, state).to_string())];
+    let mut diagnostics = Vec::new();
+    for (position, observation) in history.iter().enumerate() {
+        let outgoing = machine.edges_directed(state, Outgoing);
+        let found = outgoing.clone().find(|e| match (e.weight(), observation) {
+            (MachineLabel::Execute { cmd, .. }, Observation::Execute { cmd: c }) => cmd == c,
+            (MachineLabel::Input { event_type }, Observation::Input { event_type: e }) => {
+                event_type == e
+            }
+            _ => false,
+        });
+        match found {
+            Some(edge) => state = edge.target(),
+            None => {
+                let name = State::new(&state_name(machine, state).to_string());
+                let code = match observation {
+                    Observation::Execute { .. } => ErrorCode::CommandNotEnabled,
+                    Observation::Input { .. } => ErrorCode::UnexpectedEvent,
+                };
+                let message =
+                    format!("{observation} at position {position} is not possible in state {name}");
+                tracing::debug!(position, "{}", message);
+                let mut d = Diagnostic::new(code, message);
+                d.state = Some(name);
+                d.position = Some(position);
+                d.expected = outgoing
+                    .map(|e| Transition {
+                        label: DiagnosticLabel::Machine(e.weight().clone()),
+                        source: State::new(&state_name(machine, e.source()).to_string()),
+                        target: State::new(&state_name(machine, e.target()).to_string()),
+                    })
+                    .collect();
+                match observation {
+                    Observation::Execute { .. } => {}
+                    Observation::Input { event_type } => d.event_types.push(event_type.clone()),
+                }
+                diagnostics.push(d);
+            }
+        }
+        states.push(State::new(&state_name(machine, state).to_string()));
+    }
+    MachineReplay {
+        states,
+        diagnostics,
+    }
+}
+
+fn unexpected(
+    graph: &Graph,
+    state: NodeId,
+    position: usize,
+    message: String,
+    expected: Vec<EdgeId>,
+) -> Diagnostic {
+    let mut d = Diagnostic::new(ErrorCode::UnexpectedEvent, message);
+    d.state = Some(graph[state].clone());
+    d.position = Some(position);
+    d.expected = expected
+        .into_iter()
+        .map(|edge| {
+            let (source, target) = graph.edge_endpoints(edge).unwrap();
+            Transition {
+                label: DiagnosticLabel::Swarm(graph[edge].clone()),
+                source: graph[source].clone(),
+                target: graph[target].clone(),
+            }
+        })
+        .collect();
+    d
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{swarm, text::parse_swarm, Subscriptions};
+    use pretty_assertions::assert_eq;
+
+    fn log(entries: &[(&str, &str)]) -> Vec<(EventType, Role)> {
+        entries
+            .iter()
+            .map(|(e, r)| (EventType::new(e), Role::new(r)))
+            .collect()
+    }
+
+    fn door() -> (Graph, NodeId) {
+        let proto = parse_swarm(
+            "initial Closed\n\
+             (Closed) --[open@Control<opening>]--> (Opening)\n\
+             (Opening) --[update@Door<opening,progress>]--> (Opening)\n\
+             (Opening) --[finish@Door<opened>]--> (Open)\n",
+        )
+        .unwrap();
+        let (graph, initials, errors) = swarm::check(proto, &Subscriptions::new());
+        assert!(errors
+            .iter()
+            .all(|e| !matches!(e, swarm::Error::LogTypeEmpty(_))));
+        (graph, initials.unwrap()[0])
+    }
+
+    #[test]
+    fn conforming() {
+        let (graph, initial) = door();
+        let result = replay(
+            &graph,
+            initial,
+            &log(&[
+                ("opening", "Control"),
+                ("opening", "Door"),
+                ("progress", "Door"),
+                ("opened", "Door"),
+            ]),
+        );
+        assert_eq!(
+            result,
+            Ok(Replay {
+                state: State::new("Open"),
+                pending: None
+            })
+        );
+
+        let result = replay(
+            &graph,
+            initial,
+            &log(&[("opening", "Control"), ("opening", "Door")]),
+        )
+        .unwrap();
+        assert_eq!(result.state, State::new("Opening"));
+        assert_eq!(
+            result.pending.unwrap().label.to_string(),
+            "update@Door<opening,progress>"
+        );
+    }
+
+    #[test]
+    fn violation() {
+        let (graph, initial) = door();
+        let d = replay(
+            &graph,
+            initial,
+            &log(&[("opening", "Control"), ("opened", "Control")]),
+        )
+        .unwrap_err()
+        .remove(0);
+        assert_eq!(d.code, ErrorCode::UnexpectedEvent);
+        assert_eq!(
+            d.message,
+            "event type opened from role Control at log position 1 is not allowed in state Opening"
+        );
+        assert_eq!(d.state, Some(State::new("Opening")));
+        assert_eq!(d.position, Some(1));
+        assert_eq!(
+            d.expected
+                .iter()
+                .map(|t| t.label.to_string())
+                .collect::<Vec<_>>(),
+            vec!["finish@Door<opened>", "update@Door<opening,progress>"]
+        );
+
+        let d = replay(
+            &graph,
+            initial,
+            &log(&[
+                ("opening", "Control"),
+                ("opening", "Door"),
+                ("opened", "Door"),
+            ]),
+        )
+        .unwrap_err()
+        .remove(0);
+        assert_eq!(
+            d.message,
+            "event type opened from role Door at log position 2 does not continue transition (Opening)--[update@Door<opening,progress>]-->(Opening)"
+        );
+        assert_eq!(d.expected.len(), 1);
+    }
+
+    #[test]
+    fn machine() {
+        let (graph, initial) = door();
+        let subs = serde_json::from_str(
+            r#"{ "Control": ["opening", "opened"], "Door": ["opening", "progress", "opened"] }"#,
+        )
+        .unwrap();
+        let (proj, proj_initials) =
+            machine::project(&graph, &[initial], &subs, Role::new("Control"));
+        let history = serde_json::from_str::<Vec<Observation>>(
+            r#"[
+                { "tag": "Execute", "cmd": "open" },
+                { "tag": "Input", "eventType": "opening" },
+                { "tag": "Input", "eventType": "progress" },
+                { "tag": "Execute", "cmd": "open" },
+                { "tag": "Input", "eventType": "opened" }
+            ]"#,
+        )
+        .unwrap();
+        let result = replay_machine(&proj, proj_initials[0], &history);
+        assert_eq!(
+            result
+                .states
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>(),
+            vec!["Closed", "Closed", "Opening", "Opening", "Opening", "Open"]
+        );
+        assert_eq!(
+            result
+                .diagnostics
+                .iter()
+                .map(|d| (d.code, d.message.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    ErrorCode::UnexpectedEvent,
+                    "event type progress at position 2 is not possible in state Opening"
+                ),
+                (
+                    ErrorCode::CommandNotEnabled,
+                    "command open at position 3 is not possible in state Opening"
+                ),
+            ]
+        );
+        assert_eq!(
+            result.diagnostics[0]
+                .expected
+                .iter()
+                .map(|t| t.label.to_string())
+                .collect::<Vec<_>>(),
+            vec!["opened?", "opening?"]
+        );
+    }
+}
@@ -0,0 +1,500 @@
+//! Checking event payloads against the JSON Schemas declared for their event types
+//!
+//! A protocol may declare a schema for each event type in its `schemas` section. The checker
+//! implements the following part of JSON Schema: `type`, `enum`, `const`, `properties`,
+//! `required`, `additionalProperties`, `minProperties`, `maxProperties`, `items` (also in tuple
+//! form), `additionalItems`, `minItems`, `maxItems`, `uniqueItems`, `minimum`, `maximum`,
+//! `exclusiveMinimum`, `exclusiveMaximum`, `multipleOf`, `minLength`, `maxLength`, `allOf`,
+//! `anyOf`, `oneOf`, `not`, and `if`/`then`/`else`, as well as the schemas `true` and `false` and
+//! purely descriptive keywords like `title`. Schemas using any other keyword (e.g. `$ref`,
+//! `pattern`, or `format`) or malformed ones are reported by [`check_schema`] instead of being
+//! checked only partially.
+
+use crate::types::{Diagnostic, ErrorCode, EventType, Schema};
+use itertools::Itertools;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+const TYPES: [&str; 7] = [
+    "null", "boolean", "object", "array", "number", "string", "integer",
+];
+const ANNOTATIONS: [&str; 8] = [
+    "$schema",
+    "$id",
+    "$comment",
+    "title",
+    "description",
+    "default",
+    "examples",
+    "deprecated",
+];
+
+/// Check the payloads of a recorded log against the schemas, returning one diagnostic per
+/// problem found.
+///
+/// Events whose type has no schema are accepted with any payload. Schemas that cannot be checked
+/// are reported as [`ErrorCode::SchemaInvalid`], the payloads of their event types are not
+/// checked.
+pub fn validate_log(
+    schemas: &BTreeMap<EventType, Schema>,
+    log: &[(EventType, Value)],
+) -> Vec<Diagnostic> {
+    let _span = tracing::debug_span!("validate_log").entered();
+    let mut diagnostics = Vec::new();
+    let mut usable = BTreeMap::new();
+    for (event_type, Schema(schema)) in schemas {
+        let problems = check_schema(schema);
+        if problems.is_empty() {
+            usable.insert(event_type, schema);
+        }
+        for (path, problem) in problems {
+            let mut d = Diagnostic::new(
+                ErrorCode::SchemaInvalid,
+                format!("schema of event type {event_type}: {problem} at {path}"),
+            );
+            d.event_types.push(event_type.clone());
+            diagnostics.push(d);
+        }
+    }
+    for (position, (event_type, payload)) in log.iter().enumerate() {
+        let Some(schema) = usable.get(event_type) else {
+            continue;
+        };
+        for (path, problem) in validate(schema, payload) {
+            let mut d = Diagnostic::new(
+                ErrorCode::PayloadInvalid,
+                format!("payload of event type {event_type} at log position {position}: {problem} at {path}"),
+            );
+            d.event_types.push(event_type.clone());
+            d.position = Some(position);
+            diagnostics.push(d);
+        }
+    }
+    diagnostics
+}
+
+/// Check that the schema is well-formed and only uses the supported keywords, returning the
+/// problems together with their location in the schema, e.g. `#/properties/price`.
+pub fn check_schema(schema: &Value) -> Vec<(String, String)> {
+    let mut problems = Vec::new();
+    check_subschema(schema, "#", &mut problems);
+    problems
+}
+
+fn check_subschema(schema: &Value, path: &str, problems: &mut Vec<(String, String)>) {
+    let schema = match schema {
+        Value::Bool(_) => return,
+        Value::Object(schema) => schema,
+        other => {
+            let problem = format!("expected a schema, found {}", kind(other));
+            return problems.push((path.to_owned(), problem));
+        }
+    };
+    for (key, value) in schema {
+        let at = format!("{path}/{key}");
+        let mut problem = |p: &str| problems.push((at.clone(), p.to_owned()));
+        match key.as_str() {
+            "type" => {
+                let names = match value {
+                    Value::Array(names) => names.iter().collect(),
+                    name => vec![name],
+                };
+                let known = |n: &&Value| n.as_str().is_some_and(|n| TYPES.contains(&n));
+                if !names.iter().all(known) {
+                    problem("expected type names");
+                }
+            }
+            "enum" if !value.is_array() => problem("expected an array"),
+            "required"
+                if !value
+                    .as_array()
+                    .is_some_and(|a| a.iter().all(Value::is_string)) =>
+            {
+                problem("expected an array of property names")
+            }
+            "minimum" | "maximum" | "exclusiveMinimum" | "exclusiveMaximum"
+                if !value.is_number() =>
+            {
+                problem("expected a number")
+            }
+            "multipleOf" if !value.as_f64().is_some_and(|n| n > 0.0) => {
+                problem("expected a positive number")
+            }
+            "minLength" | "maxLength" | "minItems" | "maxItems" | "minProperties"
+            | "maxProperties"
+                if !value.is_u64() =>
+            {
+                problem("expected a non-negative integer")
+            }
+            "uniqueItems" if !value.is_boolean() => problem("expected a boolean"),
+            "enum" | "const" | "required" | "minimum" | "maximum" | "exclusiveMinimum"
+            | "exclusiveMaximum" | "multipleOf" | "minLength" | "maxLength" | "minItems"
+            | "maxItems" | "minProperties" | "maxProperties" | "uniqueItems" => {}
+            "properties" => match value.as_object() {
+                Some(props) => {
+                    for (name, prop) in props {
+                        check_subschema(prop, &format!("{at}/{name}"), problems);
+                    }
+                }
+                None => problem("expected an object"),
+            },
+            "items" => match value {
+                Value::Array(items) => {
+                    for (idx, item) in items.iter().enumerate() {
+                        check_subschema(item, &format!("{at}/{idx}"), problems);
+                    }
+                }
+                item => check_subschema(item, &at, problems),
+            },
+            "allOf" | "anyOf" | "oneOf" => match value.as_array() {
+                Some(schemas) if !schemas.is_empty() => {
+                    for (idx, schema) in schemas.iter().enumerate() {
+                        check_subschema(schema, &format!("{at}/{idx}"), problems);
+                    }
+                }
+                _ => problem("expected a non-empty array of schemas"),
+            },
+            "additionalProperties" | "additionalItems" | "not" | "if" | "then" | "else" => {
+                check_subschema(value, &at, problems)
+            }
+            key if ANNOTATIONS.contains(&key) => {}
+            key => problem(&format!("unsupported keyword {key}")),
+        }
+    }
+}
+
+/// Check a single value against a schema, returning the problems together with the path of the
+/// offending part of the value, e.g. `$.items[2]`.
+///
+/// The schema must have passed [`check_schema`], anything else in it is ignored.
+pub fn validate(schema: &Value, value: &Value) -> Vec<(String, String)> {
+    let mut problems = Vec::new();
+    check(schema, value, "$", &mut problems);
+    problems
+}
+
+fn check(schema: &Value, value: &Value, path: &str, problems: &mut Vec<(String, String)>) {
+    let mut problem = |p: String| problems.push((path.to_owned(), p));
+    let schema = match schema {
+        Value::Bool(false) => return problem("no value is allowed".to_owned()),
+        Value::Object(schema) => schema,
+        // `true`, anything else is rejected by `check_schema`
+        _ => return,
+    };
+
+    if let Some(types) = schema.get("type") {
+        let types = match types {
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            types => types.as_str().into_iter().collect::<Vec<_>>(),
+        };
+        if !types.iter().any(|t| has_type(value, t)) {
+            let kind = kind(value);
+            return problem(format!("expected {}, found {kind}", types.join(" or ")));
+        }
+    }
+    if let Some(Value::Array(allowed)) = schema.get("enum") {
+        if !allowed.contains(value) {
+            problem("value is not one of the allowed values".to_owned());
+        }
+    }
+    if let Some(constant) = schema.get("const") {
+        if constant != value {
+            problem("value differs from the required constant".to_owned());
+        }
+    }
+
+    match value {
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or(f64::NAN);
+            let bound = |key: &str| schema.get(key).and_then(Value::as_f64);
+            if let Some(min) = bound("minimum").filter(|min| n < *min) {
+                problem(format!("{n} is less than the minimum {min}"));
+            }
+            if let Some(max) = bound("maximum").filter(|max| n > *max) {
+                problem(format!("{n} is greater than the maximum {max}"));
+            }
+            if let Some(min) = bound("exclusiveMinimum").filter(|min| n <= *min) {
+                problem(format!("{n} is not greater than {min}"));
+            }
+            if let Some(max) = bound("exclusiveMaximum").filter(|max| n >= *max) {
+                problem(format!("{n} is not less than {max}"));
+            }
+            if let Some(m) = bound("multipleOf") {
+                let q = n / m;
+                // tolerate rounding errors like 0.3 / 0.1 = 2.9999999999999996
+                if (q - q.round()).abs() > 1e-9 * q.abs().max(1.0) {
+                    problem(format!("{n} is not a multiple of {m}"));
+                }
+            }
+        }
+        Value::String(s) => {
+            let len = s.chars().count() as u64;
+            let bound = |key: &str| schema.get(key).and_then(Value::as_u64);
+            if let Some(min) = bound("minLength").filter(|min| len < *min) {
+                problem(format!("string is shorter than {min} characters"));
+            }
+            if let Some(max) = bound("maxLength").filter(|max| len > *max) {
+                problem(format!("string is longer than {max} characters"));
+            }
+        }
+        Value::Array(items) => {
+            let len = items.len() as u64;
+            let bound = |key: &str| schema.get(key).and_then(Value::as_u64);
+            if let Some(min) = bound("minItems").filter(|min| len < *min) {
+                problem(format!("array has fewer than {min} items"));
+            }
+            if let Some(max) = bound("maxItems").filter(|max| len > *max) {
+                problem(format!("array has more than {max} items"));
+            }
+            if schema.get("uniqueItems") == Some(&Value::Bool(true))
+                && items.iter().tuple_combinations().any(|(a, b)| a == b)
+            {
+                problem("array items are not unique".to_owned());
+            }
+            let (tuple, rest) = match schema.get("items") {
+                Some(Value::Array(tuple)) => (tuple.as_slice(), schema.get("additionalItems")),
+                item => (&[][..], item),
+            };
+            for (idx, value) in items.iter().enumerate() {
+                if let Some(item) = tuple.get(idx).or(rest) {
+                    check(item, value, &format!("{path}[{idx}]"), problems);
+                }
+            }
+        }
+        Value::Object(props) => {
+            let len = props.len() as u64;
+            let bound = |key: &str| schema.get(key).and_then(Value::as_u64);
+            if let Some(min) = bound("minProperties").filter(|min| len < *min) {
+                problem(format!("object has fewer than {min} properties"));
+            }
+            if let Some(max) = bound("maxProperties").filter(|max| len > *max) {
+                problem(format!("object has more than {max} properties"));
+            }
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for name in required.iter().filter_map(Value::as_str) {
+                    if !props.contains_key(name) {
+                        problem(format!("missing property {name}"));
+                    }
+                }
+            }
+            let declared = schema.get("properties").and_then(Value::as_object);
+            for (name, value) in props {
+                let path = format!("{path}.{name}");
+                match declared.and_then(|d| d.get(name)) {
+                    Some(prop) => check(prop, value, &path, problems),
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            problems.push((path, format!("unexpected property {name}")))
+                        }
+                        Some(additional) => check(additional, value, &path, problems),
+                        None => {}
+                    },
+                }
+            }
+        }
+        Value::Null | Value::Bool(_) => {}
+    }
+
+    let matching = |key: &str| {
+        schema.get(key).and_then(Value::as_array).map(|schemas| {
+            schemas
+                .iter()
+                .filter(|s| validate(s, value).is_empty())
+                .count()
+        })
+    };
+    if let Some(Value::Array(schemas)) = schema.get("allOf") {
+        for s in schemas {
+            check(s, value, path, problems);
+        }
+    }
+    if matching("anyOf") == Some(0) {
+        problems.push((
+            path.to_owned(),
+            "value matches none of the alternatives".to_owned(),
+        ));
+    }
+    if let Some(count) = matching("oneOf").filter(|count| *count != 1) {
+        problems.push((
+            path.to_owned(),
+            format!("value matches {count} of the alternatives instead of exactly one"),
+        ));
+    }
+    if let Some(not) = schema.get("not") {
+        if validate(not, value).is_empty() {
+            problems.push((
+                path.to_owned(),
+                "value matches a forbidden schema".to_owned(),
+            ));
+        }
+    }
+    if let Some(condition) = schema.get("if") {
+        let branch = match validate(condition, value).is_empty() {
+            true => schema.get("then"),
+            false => schema.get("else"),
+        };
+        if let Some(branch) = branch {
+            check(branch, value, path, problems);
+        }
+    }
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match (name, value) {
+        ("integer", Value::Number(n)) => {
+            n.is_i64() || n.is_u64() || n.as_f64().is_some_and(|f| f.fract() == 0.0)
+        }
+        ("number", Value::Number(_)) => true,
+        _ => kind(value) == name,
+    }
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn payloads() {
+        let schemas = serde_json::from_str(
+            r#"{
+                "Bid": {
+                    "type": "object",
+                    "properties": {
+                        "price": { "type": "number", "minimum": 0 },
+                        "eta": { "type": "integer" },
+                        "tags": { "type": "array", "items": { "type": "string", "maxLength": 3 } }
+                    },
+                    "required": ["price"],
+                    "additionalProperties": false
+                },
+                "Selected": { "enum": ["accepted", "rejected"] }
+            }"#,
+        )
+        .unwrap();
+        let log = serde_json::from_str::<Vec<(EventType, Value)>>(
+            r#"[
+                ["Requested", { "anything": "goes" }],
+                ["Bid", { "price": 12.5, "eta": 3, "tags": ["eco"] }],
+                ["Bid", { "price": -1, "eta": 2.5, "tags": ["fast", 7], "note": "" }],
+                ["Bid", {}],
+                ["Selected", "maybe"]
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            validate_log(&schemas, &log)
+                .into_iter()
+                .map(|d| d.message)
+                .collect::<Vec<_>>(),
+            vec![
+                "payload of event type Bid at log position 2: expected integer, found number at $.eta",
+                "payload of event type Bid at log position 2: unexpected property note at $.note",
+                "payload of event type Bid at log position 2: -1 is less than the minimum 0 at $.price",
+                "payload of event type Bid at log position 2: string is longer than 3 characters at $.tags[0]",
+                "payload of event type Bid at log position 2: expected string, found number at $.tags[1]",
+                "payload of event type Bid at log position 3: missing property price at $",
+                "payload of event type Selected at log position 4: value is not one of the allowed values at $",
+            ]
+        );
+    }
+
+    #[test]
+    fn keywords() {
+        let schemas = serde_json::from_str(
+            r#"{
+                "Path": {
+                    "type": "array",
+                    "items": [{ "type": "string" }, { "type": "number", "multipleOf": 0.1 }],
+                    "additionalItems": false,
+                    "uniqueItems": true
+                },
+                "Rating": {
+                    "title": "a rating",
+                    "if": { "required": ["stars"] },
+                    "then": { "properties": { "stars": { "maximum": 5 } } },
+                    "else": { "required": ["comment"] }
+                }
+            }"#,
+        )
+        .unwrap();
+        let log = serde_json::from_str::<Vec<(EventType, Value)>>(
+            r#"[
+                ["Path", ["start", 0.3]],
+                ["Path", ["start", 0.35, "end"]],
+                ["Rating", { "stars": 6 }],
+                ["Rating", {}]
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            validate_log(&schemas, &log)
+                .into_iter()
+                .map(|d| d.message)
+                .collect::<Vec<_>>(),
+            vec![
+                "payload of event type Path at log position 1: 0.35 is not a multiple of 0.1 at $[1]",
+                "payload of event type Path at log position 1: no value is allowed at $[2]",
+                "payload of event type Rating at log position 2: 6 is greater than the maximum 5 at $.stars",
+                "payload of event type Rating at log position 3: missing property comment at $",
+            ]
+        );
+    }
+
+    #[test]
+    fn unsupported() {
+        let schemas = serde_json::from_str(
+            r#"{
+                "Bid": "string",
+                "Selected": {
+                    "properties": { "name": { "type": "string", "pattern": "^[A-Z]" } },
+                    "required": "name"
+                },
+                "Started": { "type": "date" }
+            }"#,
+        )
+        .unwrap();
+        let log = serde_json::from_str::<Vec<(EventType, Value)>>(
+            r#"[["Bid", 42], ["Selected", { "name": "bob" }]]"#,
+        )
+        .unwrap();
+        // the payloads cannot be checked, so only the schemas are reported
+        assert_eq!(
+            validate_log(&schemas, &log)
+                .into_iter()
+                .map(|d| (d.code, d.message))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    ErrorCode::SchemaInvalid,
+                    "schema of event type Bid: expected a schema, found string at #".to_owned()
+                ),
+                (
+                    ErrorCode::SchemaInvalid,
+                    "schema of event type Selected: unsupported keyword pattern at #/properties/name/pattern"
+                        .to_owned()
+                ),
+                (
+                    ErrorCode::SchemaInvalid,
+                    "schema of event type Selected: expected an array of property names at #/required"
+                        .to_owned()
+                ),
+                (
+                    ErrorCode::SchemaInvalid,
+                    "schema of event type Started: expected type names at #/type".to_owned()
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,677 @@
+//! Seeded random executions of swarm protocols
+
+use crate::{
+    machine,
+    monitor::{self, Observation},
+    types::{Command, Diagnostic, ErrorCode, EventType, Role, State, SwarmLabel},
+    EdgeId, Graph, NodeId, Subscriptions,
+};
+use petgraph::{visit::EdgeRef, Direction::Outgoing};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulationOptions {
+    pub seed: u64,
+    /// number of commands after which the simulation stops
+    #[serde(default = "default_max_steps")]
+    pub max_steps: usize,
+    /// largest number of ticks an event may take to reach a subscriber, one command is executed
+    /// per tick
+    #[serde(default)]
+    pub max_delay: u64,
+    /// whether events may overtake each other on the way to a role
+    #[serde(default)]
+    pub reorder: bool,
+}
+
+fn default_max_steps() -> usize {
+    20
+}
+
+impl SimulationOptions {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            max_steps: default_max_steps(),
+            max_delay: 0,
+            reorder: false,
+        }
+    }
+}
+
+/// Trace of a simulated execution
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Simulation {
+    pub seed: u64,
+    /// commands in the order in which they were executed
+    pub commands: Vec<SimulatedCommand>,
+    /// the resulting event log, in the format accepted by [`monitor::replay`]
+    pub log: Vec<(EventType, Role)>,
+    /// events in the order in which they reached the subscribed roles
+    pub deliveries: Vec<Delivery>,
+    /// the final state of the swarm protocol
+    pub state: State,
+    /// the final state of each role’s projection
+    pub roles: BTreeMap<Role, State>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulatedCommand {
+    pub time: u64,
+    pub role: Role,
+    pub cmd: Command,
+    /// indices of the emitted events in the log
+    pub events: Vec<usize>,
+    pub source: State,
+    pub target: State,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Delivery {
+    pub time: u64,
+    pub role: Role,
+    /// index of the event in the log
+    pub event: usize,
+    pub event_type: EventType,
+    /// state of the role’s projection after applying all events received so far in log order
+    pub state: State,
+}
+
+/// Execute the swarm protocol by repeatedly picking a role with enabled commands and one of its
+/// commands, then deliver the emitted events to all roles subscribed to them.
+///
+/// Events reach the emitting role immediately and other roles after a random delay of up to
+/// [`SimulationOptions::max_delay`] ticks. Unless [`SimulationOptions::reorder`] is set, each
+/// role receives the events in log order. The projections apply the received events in log
+/// order, like a machine runner that travels back in time when an event arrives late.
+///
+/// If the swarm protocol has several initial states, the execution starts in a random one.
+pub fn simulate(
+    swarm: &Graph,
+    initials: &[NodeId],
+    subs: &Subscriptions,
+    options: &SimulationOptions,
+) -> Simulation {
+    let _span = tracing::debug_span!("simulate", seed = options.seed).entered();
+    let mut rng = Rng::new(options.seed);
+    let start = match initials.len() {
+        1 => 0,
+        n => rng.below(n),
+    };
+    let mut state = initials[start];
+    let mut commands = Vec::new();
+    let mut log = Vec::new();
+    // (arrival time, tie breaker, role, log index)
+    let mut arrivals = Vec::new();
+    let mut last_arrival = BTreeMap::<&Role, u64>::new();
+
+    for time in 0..options.max_steps as u64 {
+        let edges = swarm.edges_directed(state, Outgoing).collect::<Vec<_>>();
+        let active = edges
+            .iter()
+            .map(|e| &e.weight().role)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        if active.is_empty() {
+            break;
+        }
+        let role = active[rng.below(active.len())];
+        let enabled = edges
+            .iter()
+            .filter(|e| e.weight().role == *role)
+            .collect::<Vec<_>>();
+        let edge = enabled[rng.below(enabled.len())];
+        let label = edge.weight();
+        tracing::debug!(time, %label, "executing");
+
+        let events = (log.len()..log.len() + label.log_type.len()).collect();
+        for event_type in &label.log_type {
+            let idx = log.len();
+            log.push((event_type.clone(), role.clone()));
+            for (subscriber, types) in subs {
+                if !types.contains(event_type) {
+                    continue;
+                }
+                let delay = if subscriber == role {
+                    0
+                } else {
+                    rng.up_to(options.max_delay)
+                };
+                let mut arrival = time.saturating_add(delay);
+                let tie = if options.reorder {
+                    rng.next_u64()
+                } else {
+                    let last = last_arrival.entry(subscriber).or_default();
+                    arrival = arrival.max(*last);
+                    *last = arrival;
+                    idx as u64
+                };
+                arrivals.push((arrival, tie, subscriber, idx));
+            }
+        }
+        commands.push(SimulatedCommand {
+            time,
+            role: role.clone(),
+            cmd: label.cmd.clone(),
+            events,
+            source: swarm[state].clone(),
+            target: swarm[edge.target()].clone(),
+        });
+        state = edge.target();
+    }
+
+    let machines = subs
+        .keys()
+        .map(|role| (role, machine::project(swarm, initials, subs, role.clone())))
+        .collect::<BTreeMap<_, _>>();
+    let replay = |role: &Role, received: &BTreeSet<usize>| {
+        let (proj, proj_initials) = &machines[role];
+        let inputs = received
+            .iter()
+            .map(|idx| Observation::Input {
+                event_type: log[*idx].0.clone(),
+            })
+            .collect::<Vec<_>>();
+        let states = monitor::replay_machine(proj, proj_initials[start], &inputs).states;
+        states.last().unwrap().clone()
+    };
+
+    arrivals.sort();
+    let mut received = BTreeMap::<&Role, BTreeSet<usize>>::new();
+    let mut deliveries = Vec::new();
+    for (time, _, role, event) in arrivals {
+        let received = received.entry(role).or_default();
+        received.insert(event);
+        deliveries.push(Delivery {
+            time,
+            role: role.clone(),
+            event,
+            event_type: log[event].0.clone(),
+            state: replay(role, received),
+        });
+    }
+    let empty = BTreeSet::new();
+    let roles = subs
+        .keys()
+        .map(|role| {
+            let received = received.get(role).unwrap_or(&empty);
+            (role.clone(), replay(role, received))
+        })
+        .collect();
+
+    Simulation {
+        seed: options.seed,
+        commands,
+        log,
+        deliveries,
+        state: swarm[state].clone(),
+        roles,
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConflictOptions {
+    pub seed: u64,
+    /// the state in which the conflicting commands are issued, by default the first state
+    /// reachable from the initial ones that has more than one transition
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<State>,
+    /// largest difference between the clocks of the replicas
+    #[serde(default = "default_max_skew")]
+    pub max_skew: u64,
+}
+
+fn default_max_skew() -> u64 {
+    3
+}
+
+impl ConflictOptions {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            state: None,
+            max_skew: default_max_skew(),
+        }
+    }
+}
+
+/// Outcome of issuing competing commands on partitioned replicas and merging their logs
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConflictSimulation {
+    pub seed: u64,
+    /// the state in which the commands were issued
+    pub state: State,
+    /// the command issued on each replica
+    pub replicas: Vec<SwarmLabel>,
+    /// the events of all replicas in timestamp order, including the history leading to `state`
+    pub merged: Vec<MergedEvent>,
+    /// the state of the swarm protocol after applying the accepted events
+    pub resolved: State,
+    /// for each role the state its projection should reach and the one it actually reaches
+    pub roles: BTreeMap<Role, Resolution>,
+    /// roles whose projection does not end up where the accepted events lead it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct MergedEvent {
+    pub timestamp: u64,
+    /// the replica that emitted the event, `None` for the shared history
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replica: Option<usize>,
+    pub event_type: EventType,
+    pub role: Role,
+    /// whether the event takes effect in the swarm protocol; events of a transition whose guard
+    /// lost against a concurrent transition are discarded
+    pub accepted: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Resolution {
+    /// state reached by the accepted events the role subscribes to
+    pub expected: State,
+    /// state reached by all merged events the role subscribes to, skipping unexpected ones
+    pub actual: State,
+}
+
+/// Issue competing commands from the same state on partitioned replicas, one per role that has
+/// enabled commands there (or two for a single role), then merge their logs in timestamp order.
+///
+/// The first guard event in the merged log decides which transition takes effect, the events of
+/// the other transitions are discarded. Each role’s projection however only sees the merged
+/// events it subscribes to, so it may follow a discarded transition if it does not see the
+/// winning guard; such roles are reported as [`ErrorCode::ConflictUnresolved`]. Well-formedness
+/// does not rule this out for roles that are not involved after the winning transition, but
+/// events such a role emits in the wrong state will be discarded in turn.
+pub fn simulate_conflict(
+    swarm: &Graph,
+    initials: &[NodeId],
+    subs: &Subscriptions,
+    options: &ConflictOptions,
+) -> Result<ConflictSimulation, Vec<Diagnostic>> {
+    let _span = tracing::debug_span!("simulate_conflict", seed = options.seed).entered();
+    let mut rng = Rng::new(options.seed);
+
+    // shortest paths from the initial states (given by their index), in breadth-first order
+    let mut paths = HashMap::<NodeId, (usize, Vec<EdgeId>)>::new();
+    let mut order = Vec::new();
+    for (idx, initial) in initials.iter().enumerate() {
+        if !paths.contains_key(initial) {
+            paths.insert(*initial, (idx, Vec::new()));
+            order.push(*initial);
+        }
+    }
+    let mut queue = order.iter().copied().collect::<VecDeque<_>>();
+    while let Some(node) = queue.pop_front() {
+        for edge in swarm.edges_directed(node, Outgoing) {
+            if !paths.contains_key(&edge.target()) {
+                let (start, mut path) = paths[&node].clone();
+                path.push(edge.id());
+                paths.insert(edge.target(), (start, path));
+                order.push(edge.target());
+                queue.push_back(edge.target());
+            }
+        }
+    }
+    let choice = |n: &NodeId| swarm.edges_directed(*n, Outgoing).count() > 1;
+    let state = match &options.state {
+        Some(name) => order.iter().find(|n| swarm[**n] == *name && choice(n)),
+        None => order.iter().find(|n| choice(n)),
+    };
+    let Some(&state) = state else {
+        let message = match &options.state {
+            Some(name) => format!("state {name} is unreachable or has no competing transitions"),
+            None => "no reachable state has competing transitions".to_owned(),
+        };
+        return Err(vec![Diagnostic::new(ErrorCode::NoConflict, message)]);
+    };
+
+    // one transition per role, or two of the same role if it is the only active one
+    let mut by_role = BTreeMap::<&Role, Vec<EdgeId>>::new();
+    for edge in swarm.edges_directed(state, Outgoing) {
+        by_role
+            .entry(&edge.weight().role)
+            .or_default()
+            .push(edge.id());
+    }
+    let replicas = if by_role.len() > 1 {
+        by_role
+            .values()
+            .map(|edges| edges[rng.below(edges.len())])
+            .collect::<Vec<_>>()
+    } else {
+        let mut edges = by_role.into_values().next().unwrap_or_default();
+        let first = edges.remove(rng.below(edges.len()));
+        vec![first, edges[rng.below(edges.len())]]
+    };
+
+    // (timestamp, replica, edge, index in the log of the edge)
+    let mut events = Vec::new();
+    let mut time = 0u64;
+    let (start, path) = &paths[&state];
+    for edge in path {
+        for idx in 0..swarm[*edge].log_type.len() {
+            events.push((time, None, *edge, idx));
+            time += 1;
+        }
+    }
+    for (replica, edge) in replicas.iter().enumerate() {
+        let start = time.saturating_add(rng.up_to(options.max_skew));
+        for idx in 0..swarm[*edge].log_type.len() {
+            events.push((start.saturating_add(idx as u64), Some(replica), *edge, idx));
+        }
+    }
+    events.sort();
+
+    // apply the events to the swarm protocol, each replica only continues its own transition
+    let mut current = initials[*start];
+    let mut pending = None::<(Option<usize>, EdgeId, usize)>;
+    let mut merged = Vec::new();
+    for (timestamp, replica, edge, idx) in events {
+        let (source, target) = swarm.edge_endpoints(edge).unwrap();
+        let accepted = match pending {
+            Some(p) => p == (replica, edge, idx),
+            None => idx == 0 && source == current,
+        };
+        if accepted {
+            if idx + 1 == swarm[edge].log_type.len() {
+                current = target;
+                pending = None;
+            } else {
+                pending = Some((replica, edge, idx + 1));
+            }
+        }
+        let label = &swarm[edge];
+        tracing::debug!(timestamp, ?replica, %label, accepted, "merged");
+        merged.push(MergedEvent {
+            timestamp,
+            replica,
+            event_type: label.log_type[idx].clone(),
+            role: label.role.clone(),
+            accepted,
+        });
+    }
+
+    let mut roles = BTreeMap::new();
+    let mut diagnostics = Vec::new();
+    for (role, types) in subs {
+        let (proj, proj_initials) = machine::project(swarm, initials, subs, role.clone());
+        let replay = |accepted_only: bool| {
+            let inputs = merged
+                .iter()
+                .filter(|e| (e.accepted || !accepted_only) && types.contains(&e.event_type))
+                .map(|e| Observation::Input {
+                    event_type: e.event_type.clone(),
+                })
+                .collect::<Vec<_>>();
+            let states = monitor::replay_machine(&proj, proj_initials[*start], &inputs).states;
+            states.last().unwrap().clone()
+        };
+        let resolution = Resolution {
+            expected: replay(true),
+            actual: replay(false),
+        };
+        if resolution.expected != resolution.actual {
+            let mut d = Diagnostic::new(
+                ErrorCode::ConflictUnresolved,
+                format!(
+                    "role {role} ends up in state {} instead of {} after the conflict in state {}",
+                    resolution.actual, resolution.expected, swarm[state]
+                ),
+            );
+            d.state = Some(swarm[state].clone());
+            d.roles.push(role.clone());
+            diagnostics.push(d);
+        }
+        roles.insert(role.clone(), resolution);
+    }
+
+    Ok(ConflictSimulation {
+        seed: options.seed,
+        state: swarm[state].clone(),
+        replicas: replicas.iter().map(|e| swarm[*e].clone()).collect(),
+        merged,
+        resolved: swarm[current].clone(),
+        roles,
+        diagnostics,
+    })
+}
+
+/// SplitMix64, good enough for picking transitions and delays reproducibly
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// uniformly distributed number in `0..n`, which must not be empty
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// uniformly distributed number in `0..=max`
+    pub(crate) fn up_to(&mut self, max: u64) -> u64 {
+        match max.checked_add(1) {
+            Some(n) => self.next_u64() % n,
+            None => self.next_u64(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{swarm, text::parse_swarm};
+    use pretty_assertions::assert_eq;
+
+    fn door() -> (Graph, Vec<NodeId>, Subscriptions) {
+        let proto = parse_swarm(
+            "initial Closed\n\
+             (Closed) --[open@Control<opening>]--> (Opening)\n\
+             (Opening) --[update@Door<progress>]--> (Opening)\n\
+             (Opening) --[finish@Door<opened>]--> (Open)\n\
+             (Open) --[close@Control<closing>]--> (Closing)\n\
+             (Closing) --[finish@Door<closed>]--> (Closed)\n",
+        )
+        .unwrap();
+        let subs = serde_json::from_str(
+            r#"{
+                "Control": ["opening", "progress", "opened", "closing", "closed"],
+                "Door": ["opening", "progress", "opened", "closing", "closed"]
+            }"#,
+        )
+        .unwrap();
+        let (graph, initials, errors) = swarm::check(proto, &subs);
+        assert_eq!(errors, vec![]);
+        (graph, initials.unwrap(), subs)
+    }
+
+    #[test]
+    fn reproducible() {
+        let (graph, initials, subs) = door();
+        let options = SimulationOptions {
+            max_delay: 3,
+            ..SimulationOptions::new(7)
+        };
+        let sim = simulate(&graph, &initials, &subs, &options);
+        assert_eq!(sim, simulate(&graph, &initials, &subs, &options));
+        assert_eq!(sim.commands.len(), 20);
+        assert_eq!(
+            sim.log.len(),
+            sim.commands.iter().map(|c| c.events.len()).sum::<usize>()
+        );
+
+        // the log conforms to the protocol
+        let replay = monitor::replay(&graph, initials[0], &sim.log).unwrap();
+        assert_eq!(replay.state, sim.state);
+
+        // in-order delivery of everything means all projections end up in the final state
+        for (role, state) in &sim.roles {
+            assert_eq!(state, &sim.state, "{role}");
+            let events = sim
+                .deliveries
+                .iter()
+                .filter(|d| d.role == *role)
+                .map(|d| d.event)
+                .collect::<Vec<_>>();
+            assert!(events.windows(2).all(|w| w[0] < w[1]), "{role}: {events:?}");
+        }
+    }
+
+    #[test]
+    fn reorder() {
+        let (graph, initials, subs) = door();
+        let out_of_order = (0..20).any(|seed| {
+            let options = SimulationOptions {
+                max_delay: 5,
+                reorder: true,
+                ..SimulationOptions::new(seed)
+            };
+            let sim = simulate(&graph, &initials, &subs, &options);
+            sim.deliveries
+                .windows(2)
+                .any(|w| w[0].role == w[1].role && w[0].event > w[1].event)
+        });
+        assert!(out_of_order);
+    }
+
+    #[test]
+    fn unbounded_delay() {
+        let (graph, initials, subs) = door();
+        let options = SimulationOptions {
+            max_delay: u64::MAX,
+            ..SimulationOptions::new(3)
+        };
+        let sim = simulate(&graph, &initials, &subs, &options);
+        assert_eq!(sim.commands.len(), 20);
+        // every event still reaches both roles, however late
+        assert_eq!(sim.deliveries.len(), sim.log.len() * 2);
+    }
+
+    #[test]
+    fn stops_in_final_state() {
+        let proto = parse_swarm("initial S0\n(S0) --[a@R<A>]--> (S1)\n").unwrap();
+        let subs = serde_json::from_str(r#"{ "R": ["A"], "Q": ["A"] }"#).unwrap();
+        let (graph, initials, _) = swarm::check(proto, &subs);
+        let options = SimulationOptions {
+            max_delay: 2,
+            ..SimulationOptions::new(1)
+        };
+        let sim = simulate(&graph, &initials.unwrap(), &subs, &options);
+        assert_eq!(sim.commands.len(), 1);
+        assert_eq!(sim.state, State::new("S1"));
+        assert_eq!(
+            sim.deliveries
+                .iter()
+                .map(|d| (d.role.as_ref(), d.state.as_ref()))
+                .collect::<BTreeSet<_>>(),
+            BTreeSet::from([("Q", "S1"), ("R", "S1")])
+        );
+    }
+
+    #[test]
+    fn conflict() {
+        let proto = parse_swarm(
+            "initial S0\n\
+             (S0) --[accept@A<accepted>]--> (S1)\n\
+             (S0) --[reject@B<rejected>]--> (S2)\n\
+             (S1) --[ship@C<shipped>]--> (S3)\n",
+        )
+        .unwrap();
+        let good: Subscriptions = serde_json::from_str(
+            r#"{ "A": ["accepted", "rejected"], "B": ["accepted", "rejected"], "C": ["accepted", "rejected", "shipped"] }"#,
+        )
+        .unwrap();
+        let (graph, initials, errors) = swarm::check(proto.clone(), &good);
+        assert_eq!(errors, vec![]);
+        let initials = initials.unwrap();
+
+        for seed in 0..10 {
+            let sim =
+                simulate_conflict(&graph, &initials, &good, &ConflictOptions::new(seed)).unwrap();
+            assert_eq!(sim.state, State::new("S0"));
+            assert_eq!(sim.replicas.len(), 2);
+            assert_eq!(sim.merged.iter().filter(|e| e.accepted).count(), 1);
+            assert_eq!(sim.diagnostics, vec![], "seed {seed}");
+            assert_eq!(sim.roles[&Role::new("C")].actual, sim.resolved);
+        }
+        let skewed = ConflictOptions {
+            max_skew: u64::MAX,
+            ..ConflictOptions::new(1)
+        };
+        let sim = simulate_conflict(&graph, &initials, &good, &skewed).unwrap();
+        assert_eq!(sim.merged.iter().filter(|e| e.accepted).count(), 1);
+        assert_eq!(sim.diagnostics, vec![]);
+
+        // well-formed, but C does not learn that its transition lost
+        let unaware: Subscriptions = serde_json::from_str(
+            r#"{ "A": ["accepted", "rejected"], "B": ["accepted", "rejected"], "C": ["accepted", "shipped"] }"#,
+        )
+        .unwrap();
+        assert_eq!(swarm::check(proto, &unaware).2, vec![]);
+        let sim = (0..20)
+            .map(|seed| {
+                simulate_conflict(&graph, &initials, &unaware, &ConflictOptions::new(seed)).unwrap()
+            })
+            .find(|sim| sim.resolved == State::new("S2"))
+            .unwrap();
+        assert_eq!(
+            sim.diagnostics
+                .iter()
+                .map(|d| d.message.as_str())
+                .collect::<Vec<_>>(),
+            vec!["role C ends up in state S1 instead of S0 after the conflict in state S0"]
+        );
+
+        // C does not subscribe to the guard of the transition it depends on
+        let bad: Subscriptions = serde_json::from_str(
+            r#"{ "A": ["accepted", "rejected"], "B": ["accepted", "rejected"], "C": ["rejected", "shipped"] }"#,
+        )
+        .unwrap();
+        let sim = (0..20)
+            .map(|seed| {
+                simulate_conflict(&graph, &initials, &bad, &ConflictOptions::new(seed)).unwrap()
+            })
+            .find(|sim| sim.resolved == State::new("S1"))
+            .unwrap();
+        assert_eq!(
+            sim.diagnostics
+                .iter()
+                .map(|d| d.message.as_str())
+                .collect::<Vec<_>>(),
+            vec!["role C ends up in state S2 instead of S0 after the conflict in state S0"]
+        );
+
+        let err = simulate_conflict(
+            &graph,
+            &initials,
+            &good,
+            &ConflictOptions {
+                state: Some(State::new("S1")),
+                ..ConflictOptions::new(0)
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err[0].code, ErrorCode::NoConflict);
+    }
+}
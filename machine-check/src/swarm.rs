@@ -1,16 +1,21 @@
 use crate::{
-    types::{EventType, Role, State, StateName, SwarmLabel},
+    dfs,
+    types::{
+        Diagnostic, DiagnosticLabel, ErrorCode, EventType, Role, State, StateName, SubscriptionFix,
+        SwarmLabel, Transition,
+    },
     EdgeId, MapVec, NodeId, Subscriptions, SwarmProtocol,
 };
 use bitvec::{bitvec, vec::BitVec};
 use itertools::Itertools;
 use petgraph::{
-    visit::{Dfs, DfsPostOrder, EdgeRef, Walker},
+    visit::{DfsPostOrder, EdgeRef, Walker},
     Direction::{Incoming, Outgoing},
 };
 use std::{
     collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     fmt,
+    iter::once,
     mem::take,
 };
 
@@ -36,10 +41,10 @@ pub enum Error {
 const INVALID_EDGE: &str = "[invalid EdgeId]";
 
 impl Error {
-    fn to_string<N: StateName>(&self, graph: &petgraph::Graph<N, SwarmLabel>) -> String {
+    pub fn to_string<N: StateName>(&self, graph: &petgraph::Graph<N, SwarmLabel>) -> String {
         match self {
             Error::InitialStateDisconnected => {
-                format!("initial swarm protocol state has no transitions")
+                "initial swarm protocol state has no transitions".to_string()
             }
             Error::StateUnreachable(node) => {
                 format!(
@@ -103,10 +108,152 @@ impl Error {
     ) -> impl Fn(Error) -> String + '_ {
         |err| err.to_string(graph)
     }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Error::InitialStateDisconnected => ErrorCode::InitialStateDisconnected,
+            Error::StateUnreachable(_) => ErrorCode::StateUnreachable,
+            Error::LogTypeEmpty(_) => ErrorCode::LogTypeEmpty,
+            Error::ActiveRoleNotSubscribed(_) => ErrorCode::ActiveRoleNotSubscribed,
+            Error::LaterActiveRoleNotSubscribed(..) => ErrorCode::LaterActiveRoleNotSubscribed,
+            Error::LaterInvolvedRoleMoreSubscribed { .. } => {
+                ErrorCode::LaterInvolvedRoleMoreSubscribed
+            }
+            Error::LaterInvolvedNotGuarded(..) => ErrorCode::LaterInvolvedNotGuarded,
+            Error::NonDeterministicGuard(_) => ErrorCode::NonDeterministicGuard,
+            Error::NonDeterministicCommand(_) => ErrorCode::NonDeterministicCommand,
+            Error::GuardNotInvariant(_) => ErrorCode::GuardNotInvariant,
+        }
+    }
+
+    pub fn to_diagnostic<N: StateName>(
+        &self,
+        graph: &petgraph::Graph<N, SwarmLabel>,
+    ) -> Diagnostic {
+        let mut d = Diagnostic::new(self.code(), self.to_string(graph));
+        let edge = match self {
+            Error::InitialStateDisconnected
+            | Error::StateUnreachable(_)
+            | Error::GuardNotInvariant(_) => None,
+            Error::LogTypeEmpty(edge)
+            | Error::ActiveRoleNotSubscribed(edge)
+            | Error::LaterActiveRoleNotSubscribed(edge, _)
+            | Error::LaterInvolvedRoleMoreSubscribed { edge, .. }
+            | Error::LaterInvolvedNotGuarded(edge, _)
+            | Error::NonDeterministicGuard(edge)
+            | Error::NonDeterministicCommand(edge) => Some(*edge),
+        };
+        let label = edge.and_then(|edge| graph.edge_weight(edge));
+        let guard = label.and_then(|l| l.log_type.first()).cloned();
+        d.transition = edge.and_then(|edge| transition(graph, edge));
+        match self {
+            Error::InitialStateDisconnected => {}
+            Error::StateUnreachable(node) => {
+                d.state = graph.node_weight(*node).map(|n| n.state_name().clone());
+            }
+            Error::LogTypeEmpty(_) => d.roles.extend(label.map(|l| l.role.clone())),
+            Error::ActiveRoleNotSubscribed(_) => {
+                d.roles.extend(label.map(|l| l.role.clone()));
+                d.event_types = label.map(|l| l.log_type.clone()).unwrap_or_default();
+            }
+            Error::LaterActiveRoleNotSubscribed(_, role) => {
+                d.roles.push(role.clone());
+                d.event_types = label.map(|l| l.log_type.clone()).unwrap_or_default();
+            }
+            Error::LaterInvolvedRoleMoreSubscribed {
+                later,
+                active,
+                events,
+                ..
+            } => {
+                d.roles = vec![later.clone(), active.clone()];
+                d.event_types = events.iter().cloned().collect();
+            }
+            Error::LaterInvolvedNotGuarded(_, role) => {
+                d.roles.push(role.clone());
+                d.event_types.extend(guard);
+            }
+            Error::NonDeterministicGuard(_) => {
+                d.state = d.transition.as_ref().map(|t| t.source.clone());
+                d.event_types.extend(guard);
+            }
+            Error::NonDeterministicCommand(_) => {
+                d.state = d.transition.as_ref().map(|t| t.source.clone());
+                d.roles.extend(label.map(|l| l.role.clone()));
+            }
+            Error::GuardNotInvariant(ev) => d.event_types.push(ev.clone()),
+        }
+        d.fixes = self.fixes(graph);
+        d
+    }
+
+    /// Alternative changes to the subscriptions that each fix this error; the first one
+    /// only adds subscriptions.
+    pub fn fixes<N: StateName>(
+        &self,
+        graph: &petgraph::Graph<N, SwarmLabel>,
+    ) -> Vec<SubscriptionFix> {
+        let guard = |edge: &EdgeId| graph.edge_weight(*edge).map(|l| l.log_type[0].clone());
+        match self {
+            Error::ActiveRoleNotSubscribed(edge) => graph
+                .edge_weight(*edge)
+                .map(|l| SubscriptionFix::Add {
+                    role: l.role.clone(),
+                    event_types: vec![l.log_type[0].clone()],
+                })
+                .into_iter()
+                .collect(),
+            Error::LaterActiveRoleNotSubscribed(edge, role)
+            | Error::LaterInvolvedNotGuarded(edge, role) => guard(edge)
+                .map(|guard| SubscriptionFix::Add {
+                    role: role.clone(),
+                    event_types: vec![guard],
+                })
+                .into_iter()
+                .collect(),
+            Error::LaterInvolvedRoleMoreSubscribed {
+                later,
+                active,
+                events,
+                ..
+            } => vec![
+                SubscriptionFix::Add {
+                    role: active.clone(),
+                    event_types: events.iter().cloned().collect(),
+                },
+                SubscriptionFix::Remove {
+                    role: later.clone(),
+                    event_types: events.iter().cloned().collect(),
+                },
+            ],
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn diagnostic<N: StateName>(
+        graph: &petgraph::Graph<N, SwarmLabel>,
+    ) -> impl Fn(Error) -> Diagnostic + '_ {
+        |err| err.to_diagnostic(graph)
+    }
+}
+
+fn transition<N: StateName>(
+    graph: &petgraph::Graph<N, SwarmLabel>,
+    edge: EdgeId,
+) -> Option<Transition<DiagnosticLabel>> {
+    let (source, target) = graph.edge_endpoints(edge)?;
+    Some(Transition {
+        label: DiagnosticLabel::Swarm(graph[edge].clone()),
+        source: graph[source].state_name().clone(),
+        target: graph[target].state_name().clone(),
+    })
 }
 
 /// helper for printing a transition
-struct Edge<'a, N: StateName>(&'a petgraph::Graph<N, SwarmLabel>, EdgeId);
+pub(crate) struct Edge<'a, N: StateName>(
+    pub(crate) &'a petgraph::Graph<N, SwarmLabel>,
+    pub(crate) EdgeId,
+);
 
 impl<'a, N: StateName> fmt::Display for Edge<'a, N> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -121,17 +268,17 @@ impl<'a, N: StateName> fmt::Display for Edge<'a, N> {
 }
 
 #[derive(Debug)]
-struct Node {
-    name: State,
+pub(crate) struct Node {
+    pub(crate) name: State,
     /**
      * All roles that have an enabled command in this state
      */
-    active: BTreeSet<Role>,
+    pub(crate) active: BTreeSet<Role>,
     /**
      * All roles that subscribe to at least one event emitted by a transition reachable
      * from this state.
      */
-    roles: BTreeSet<Role>,
+    pub(crate) roles: BTreeSet<Role>,
 }
 
 impl Node {
@@ -164,45 +311,52 @@ impl Variance {
     }
 }
 
-type Graph = petgraph::Graph<Node, SwarmLabel>;
+pub(crate) type Graph = petgraph::Graph<Node, SwarmLabel>;
 
 pub fn check(
     proto: SwarmProtocol,
     subs: &Subscriptions,
-) -> (super::Graph, Option<NodeId>, Vec<Error>) {
-    let (graph, initial, mut errors) = match prepare_graph(proto, &subs) {
+) -> (super::Graph, Option<Vec<NodeId>>, Vec<Error>) {
+    let (graph, initials, errors) = check_annotated(proto, subs);
+    (to_swarm(&graph), initials, errors)
+}
+
+/// Same as [`check`], but keeps the active and involved roles computed for each state.
+pub(crate) fn check_annotated(
+    proto: SwarmProtocol,
+    subs: &Subscriptions,
+) -> (Graph, Option<Vec<NodeId>>, Vec<Error>) {
+    let (graph, initials, mut errors) = match prepare_graph(proto, subs) {
         (g, Some(i), e) => (g, i, e),
-        (g, None, e) => return (to_swarm(&g), None, e),
+        (g, None, e) => return (g, None, e),
     };
-    errors.extend(all_nodes_reachable(&graph, initial));
-    errors.extend(well_formed(&graph, initial, subs));
-    (to_swarm(&graph), Some(initial), errors)
+    errors.extend(all_nodes_reachable(&graph, &initials));
+    errors.extend(well_formed(&graph, &initials, subs));
+    (graph, Some(initials), errors)
 }
 
-fn to_swarm(graph: &Graph) -> super::Graph {
+pub(crate) fn to_swarm(graph: &Graph) -> super::Graph {
     graph.map(|_, n| n.name.clone(), |_, x| x.clone())
 }
 
-fn all_nodes_reachable(graph: &Graph, initial: NodeId) -> Vec<Error> {
+fn all_nodes_reachable(graph: &Graph, initials: &[NodeId]) -> Vec<Error> {
     // Traversal order choice (Bfs vs Dfs vs DfsPostOrder) does not matter
-    let visited = Dfs::new(&graph, initial)
-        .iter(&graph)
-        .collect::<BTreeSet<_>>();
+    let visited = dfs(&graph, initials).iter(&graph).collect::<BTreeSet<_>>();
 
     graph
         .node_indices()
         .filter(|node| !visited.contains(node))
-        .map(|node| Error::StateUnreachable(node))
+        .map(Error::StateUnreachable)
         .collect()
 }
 
-fn well_formed(graph: &Graph, initial: NodeId, subs: &Subscriptions) -> Vec<Error> {
+fn well_formed(graph: &Graph, initials: &[NodeId], subs: &Subscriptions) -> Vec<Error> {
     let mut errors = Vec::new();
     let empty = BTreeSet::new(); // just for `sub` but needs its own lifetime
     let sub = |r: &Role| subs.get(r).unwrap_or(&empty);
 
     // visit all reachable nodes of the graph to check their prescribed conditions; order doesn’t matter
-    for node in Dfs::new(&graph, initial).iter(&graph) {
+    for node in dfs(&graph, initials).iter(&graph) {
         let mut guards = BTreeMap::new();
         let mut commands = BTreeSet::new();
         for edge in graph.edges_directed(node, Outgoing) {
@@ -228,48 +382,199 @@ fn well_formed(graph: &Graph, initial: NodeId, subs: &Subscriptions) -> Vec<Erro
             let target = edge.target();
 
             // causal consistency
-            if log_filter(log, sub(&role)).first_one().is_none() {
+            if log_filter(log, sub(role)).first_one().is_none() {
                 errors.push(Error::ActiveRoleNotSubscribed(edge.id()));
             }
-            for active in &graph[target].active {
-                let filtered = log_filter(log, sub(active));
-                if filtered.first_one().is_none() {
-                    errors.push(Error::LaterActiveRoleNotSubscribed(
-                        edge.id(),
-                        active.clone(),
-                    ));
-                }
-                for later in &graph[target].roles {
-                    let later_log = log_filter(log, sub(later));
-                    let extra = later_log & !filtered.clone();
-                    if extra.first_one().is_some() {
-                        errors.push(Error::LaterInvolvedRoleMoreSubscribed {
-                            edge: edge.id(),
-                            later: later.clone(),
-                            active: active.clone(),
-                            events: extra.iter_ones().map(|i| log[i].clone()).collect(),
-                        });
-                    }
+            errors.extend(subscription_errors(
+                edge.id(),
+                log,
+                &graph[target].active,
+                &graph[target].roles,
+                subs,
+            ));
+        }
+    }
+    errors
+}
+
+/// Causal consistency and choice determinacy for a transition emitting `log`, given the roles
+/// that are active and involved after it.
+pub(crate) fn subscription_errors(
+    edge: EdgeId,
+    log: &[EventType],
+    active: &BTreeSet<Role>,
+    roles: &BTreeSet<Role>,
+    subs: &Subscriptions,
+) -> Vec<Error> {
+    let mut errors = Vec::new();
+    let empty = BTreeSet::new();
+    let sub = |r: &Role| subs.get(r).unwrap_or(&empty);
+
+    for active in active {
+        let filtered = log_filter(log, sub(active));
+        if filtered.first_one().is_none() {
+            errors.push(Error::LaterActiveRoleNotSubscribed(edge, active.clone()));
+        }
+        for later in roles {
+            let later_log = log_filter(log, sub(later));
+            let extra = later_log & !filtered.clone();
+            if extra.first_one().is_some() {
+                errors.push(Error::LaterInvolvedRoleMoreSubscribed {
+                    edge,
+                    later: later.clone(),
+                    active: active.clone(),
+                    events: extra.iter_ones().map(|i| log[i].clone()).collect(),
+                });
+            }
+        }
+    }
+
+    // choice determinacy
+    let guard = &log[0];
+    for later in roles {
+        if !sub(later).contains(guard) {
+            errors.push(Error::LaterInvolvedNotGuarded(edge, later.clone()));
+        }
+    }
+    errors
+}
+
+/// Compute the smallest subscriptions that extend the given ones such that the protocol
+/// satisfies causal consistency and choice determinacy.
+///
+/// Every role that is active in some transition gets an entry, possibly with event types
+/// that were not mentioned in `subs`. Errors are only returned if the protocol graph cannot
+/// be constructed; problems unrelated to subscriptions (like non-determinism) are reported
+/// by [`check`].
+pub fn infer_subscriptions(
+    proto: SwarmProtocol,
+    subs: &Subscriptions,
+) -> Result<Subscriptions, Vec<Error>> {
+    let mut subs = subs.clone();
+    for t in &proto.transitions {
+        subs.entry(t.label.role.clone()).or_default();
+    }
+    // adding subscriptions changes Node::roles, so recompute the graph until nothing changes
+    loop {
+        let (graph, initials, errors) = prepare_graph(proto.clone(), &subs);
+        let Some(initials) = initials else {
+            return Err(errors);
+        };
+        if !add_required_subscriptions(&graph, &initials, &mut subs) {
+            return Ok(subs);
+        }
+    }
+}
+
+/// Repeatedly apply the first fix of every error reported by [`check`] until the protocol is
+/// well-formed under the returned subscriptions.
+///
+/// If some errors cannot be fixed by changing subscriptions, they are returned instead.
+pub fn suggest_subscription_fixes(
+    proto: SwarmProtocol,
+    subs: &Subscriptions,
+) -> Result<Subscriptions, Vec<Error>> {
+    let mut subs = subs.clone();
+    loop {
+        let (graph, _, errors) = check(proto.clone(), &subs);
+        if errors.is_empty() {
+            return Ok(subs);
+        }
+        let mut changed = false;
+        for error in &errors {
+            // the first fix only adds subscriptions, so this terminates
+            if let Some(fix) = error.fixes(&graph).first() {
+                changed |= fix.apply(&mut subs);
+            }
+        }
+        if !changed {
+            return Err(errors);
+        }
+    }
+}
+
+/// add event types that are demanded by the rules checked in [`well_formed`], returns
+/// whether something was added
+fn add_required_subscriptions(
+    graph: &Graph,
+    initials: &[NodeId],
+    subs: &mut Subscriptions,
+) -> bool {
+    let mut changed = false;
+    for node in dfs(&graph, initials).iter(&graph) {
+        for edge in graph.edges_directed(node, Outgoing) {
+            let log = edge.weight().log_type.as_slice();
+            let guard = &log[0];
+            let target = edge.target();
+
+            // causal consistency: active roles must see at least one event (choose the guard)
+            // and must see everything a later involved role sees
+            for active in once(&edge.weight().role).chain(&graph[target].active) {
+                changed |= subscribe(subs, active, once(guard));
+            }
+            for later in &graph[target].roles {
+                let later_log = match subs.get(later) {
+                    Some(sub) => log.iter().filter(|ev| sub.contains(*ev)).cloned().collect(),
+                    None => Vec::new(),
+                };
+                for active in &graph[target].active {
+                    changed |= subscribe(subs, active, later_log.iter());
                 }
             }
 
             // choice determinacy
             for later in &graph[target].roles {
-                if !sub(later).contains(guard) {
-                    errors.push(Error::LaterInvolvedNotGuarded(edge.id(), later.clone()));
-                }
+                changed |= subscribe(subs, later, once(guard));
             }
         }
     }
-    errors
+    changed
+}
+
+fn subscribe<'a>(
+    subs: &mut Subscriptions,
+    role: &Role,
+    events: impl Iterator<Item = &'a EventType>,
+) -> bool {
+    let sub = subs.entry(role.clone()).or_default();
+    let mut changed = false;
+    for event in events {
+        if sub.insert(event.clone()) {
+            tracing::debug!(%role, %event, "adding subscription");
+            changed = true;
+        }
+    }
+    changed
 }
 
 pub fn from_json(
     proto: SwarmProtocol,
     subs: &Subscriptions,
-) -> (super::Graph, Option<NodeId>, Vec<String>) {
+) -> (super::Graph, Option<Vec<NodeId>>, Vec<Diagnostic>) {
     let (g, i, e) = prepare_graph(proto, subs);
-    (to_swarm(&g), i, e.map(Error::convert(&g)))
+    (to_swarm(&g), i, e.map(Error::diagnostic(&g)))
+}
+
+/// Turn a swarm graph back into its JSON representation.
+pub fn to_json(graph: &super::Graph, initials: &[NodeId]) -> SwarmProtocol {
+    let transitions = graph
+        .edge_references()
+        .map(|edge| Transition {
+            label: edge.weight().clone(),
+            source: graph[edge.source()].clone(),
+            target: graph[edge.target()].clone(),
+        })
+        .collect();
+    SwarmProtocol {
+        initial: initials
+            .iter()
+            .unique()
+            .map(|n| graph[*n].clone())
+            .collect(),
+        transitions,
+        final_states: Vec::new(),
+        schemas: BTreeMap::new(),
+    }
 }
 
 /// unfortunately there is no walker for neighbors, so we need to handroll it
@@ -297,7 +602,7 @@ impl Neighbors {
 fn prepare_graph(
     proto: SwarmProtocol,
     subs: &Subscriptions,
-) -> (Graph, Option<NodeId>, Vec<Error>) {
+) -> (Graph, Option<Vec<NodeId>>, Vec<Error>) {
     let mut errors = Vec::new();
     let mut graph = Graph::new();
     let mut nodes = HashMap::new();
@@ -310,24 +615,33 @@ fn prepare_graph(
             .entry(t.target.clone())
             .or_insert_with(|| graph.add_node(Node::new(t.target)));
         let edge = graph.add_edge(source, target, t.label.clone());
-        if t.label.log_type.len() == 0 {
+        if t.label.log_type.is_empty() {
             errors.push(Error::LogTypeEmpty(edge));
         }
         tracing::debug!("added {:?} --> {:?}", source, target);
     }
-    let initial = if let Some(idx) = nodes.get(&proto.initial) {
-        tracing::debug!("initial state {:?}", idx);
-        *idx
-    } else {
-        errors.push(Error::InitialStateDisconnected);
-        return (graph, None, errors);
+    let initials = proto
+        .initial
+        .iter()
+        .map(|state| nodes.get(state).copied())
+        .collect::<Option<Vec<_>>>();
+    let initials = match initials {
+        Some(initials) if !initials.is_empty() => {
+            tracing::debug!("initial states {:?}", initials);
+            initials
+        }
+        _ => {
+            errors.push(Error::InitialStateDisconnected);
+            return (graph, None, errors);
+        }
     };
     let no_empty_logs = errors.is_empty();
 
     // compute the needed Node information
     // - first post-order walk to propagate non-loop roles back
     // - then keep fixing loop-ends until graph is stable
-    let mut walk = DfsPostOrder::new(&graph, initial);
+    let mut walk = DfsPostOrder::empty(&graph);
+    walk.stack.extend(initials.iter().rev());
     let mut guards = HashSet::new();
     let mut events = HashMap::<EventType, Variance>::new();
 
@@ -337,7 +651,7 @@ fn prepare_graph(
     while let Some(node_id) = walk.next(&graph) {
         let active = active(&graph, node_id);
         graph[node_id].active = active;
-        let roles = involved(&graph, node_id, &subs, &mut change_nodes);
+        let roles = involved(&graph, node_id, subs, &mut change_nodes);
         graph[node_id].roles = roles;
         mark_events(&graph, node_id, &mut guards, &mut events);
     }
@@ -365,8 +679,8 @@ fn prepare_graph(
         }
     }
 
-    let initial = no_empty_logs.then(|| initial);
-    (graph, initial, errors)
+    let initials = no_empty_logs.then_some(initials);
+    (graph, initials, errors)
 }
 
 /// compute a first approximation of Node::roles assuming to be called in DfsPostOrder
@@ -424,7 +738,7 @@ fn mark_events(
     let _span = tracing::debug_span!("mark_events", node = %g[node].name).entered();
     for edge in g.edges_directed(node, Outgoing) {
         let log = edge.weight().log_type.as_slice();
-        if log.len() < 1 {
+        if log.is_empty() {
             continue;
         }
         guards.insert(log[0].clone());
@@ -456,7 +770,7 @@ mod tests {
     use super::*;
     use crate::MapVec;
     use maplit::btreeset;
-    use petgraph::visit::{Dfs, Walker};
+    use petgraph::visit::Walker;
     use pretty_assertions::assert_eq;
     use std::collections::BTreeMap;
     use tracing_subscriber::{fmt, fmt::format::FmtSpan, EnvFilter};
@@ -481,10 +795,10 @@ mod tests {
     fn ev(e: &str) -> EventType {
         EventType::new(e)
     }
-    fn prep_graph(proto: SwarmProtocol, subs: &Subscriptions) -> (super::Graph, NodeId) {
-        let (graph, initial, e) = prepare_graph(proto, &subs);
+    fn prep_graph(proto: SwarmProtocol, subs: &Subscriptions) -> (super::Graph, Vec<NodeId>) {
+        let (graph, initials, e) = prepare_graph(proto, subs);
         assert_eq!(e.len(), 0);
-        (graph, initial.unwrap())
+        (graph, initials.unwrap())
     }
 
     #[test]
@@ -519,9 +833,9 @@ mod tests {
         )
         .unwrap();
 
-        let (graph, initial) = prep_graph(proto, &subs);
+        let (graph, initials) = prep_graph(proto, &subs);
         let mut nodes = BTreeMap::new();
-        for node in Dfs::new(&graph, initial).iter(&graph) {
+        for node in dfs(&graph, &initials).iter(&graph) {
             let node = &graph[node];
             nodes.insert(node.name.clone(), (node.active.clone(), node.roles.clone()));
         }
@@ -549,7 +863,7 @@ mod tests {
         );
         assert_eq!(nodes["S4"].1, btreeset! {});
 
-        let mut errors = well_formed(&graph, initial, &subs);
+        let mut errors = well_formed(&graph, &initials, &subs);
         errors.sort();
         let g = &graph;
         let mut expected = vec![
@@ -701,6 +1015,169 @@ mod tests {
         ]);
     }
 
+    #[test]
+    fn typed_api() {
+        setup_logger();
+        let proto = serde_json::from_str::<SwarmProtocol>(
+            r#"{
+                "initial": "S0",
+                "transitions": [
+                    { "source": "S0", "target": "S1", "label": { "cmd": "a", "logType": ["A"], "role": "R1" } },
+                    { "source": "S1", "target": "S2", "label": { "cmd": "b", "logType": ["B"], "role": "R2" } }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let subs = serde_json::from_str::<Subscriptions>(
+            r#"{
+                "R1": ["A", "B"],
+                "R2": ["A", "B"]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(crate::check_swarm(&proto, &subs), Ok(()));
+
+        let subs =
+            serde_json::from_str::<Subscriptions>(r#"{ "R1": ["A"], "R2": ["A"] }"#).unwrap();
+        // edge indices correspond to the positions in `proto.transitions`
+        assert_eq!(
+            crate::check_swarm(&proto, &subs),
+            Err(vec![Error::ActiveRoleNotSubscribed(EdgeId::new(1))])
+        );
+    }
+
+    #[test]
+    fn diagnostics() {
+        setup_logger();
+        let proto = serde_json::from_str::<SwarmProtocol>(
+            r#"{
+                "initial": "S0",
+                "transitions": [
+                    { "source": "S0", "target": "S1", "label": { "cmd": "a", "logType": ["A"], "role": "R1" } },
+                    { "source": "S1", "target": "S2", "label": { "cmd": "b", "logType": ["B"], "role": "R2" } }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let subs = serde_json::from_str::<Subscriptions>(
+            r#"{
+                "R1": ["A", "B"],
+                "R2": ["B"]
+            }"#,
+        )
+        .unwrap();
+        let (g, _, errors) = check(proto.clone(), &subs);
+        let errors = errors.map(Error::diagnostic(&g));
+        assert_eq!(
+            serde_json::to_value(&errors).unwrap(),
+            serde_json::json!([
+                {
+                    "code": "LaterActiveRoleNotSubscribed",
+                    "message": "subsequently active role R2 does not subscribe to events in transition (S0)--[a@R1<A>]-->(S1)",
+                    "transition": {
+                        "source": "S0",
+                        "target": "S1",
+                        "label": { "cmd": "a", "logType": ["A"], "role": "R1" }
+                    },
+                    "roles": ["R2"],
+                    "eventTypes": ["A"],
+                    "fixes": [{ "action": "add", "role": "R2", "eventTypes": ["A"] }]
+                },
+                {
+                    "code": "LaterInvolvedRoleMoreSubscribed",
+                    "message": "subsequently involved role R1 subscribes to more events than active role R2 in transition (S0)--[a@R1<A>]-->(S1), namely (A)",
+                    "transition": {
+                        "source": "S0",
+                        "target": "S1",
+                        "label": { "cmd": "a", "logType": ["A"], "role": "R1" }
+                    },
+                    "roles": ["R1", "R2"],
+                    "eventTypes": ["A"],
+                    "fixes": [
+                        { "action": "add", "role": "R2", "eventTypes": ["A"] },
+                        { "action": "remove", "role": "R1", "eventTypes": ["A"] }
+                    ]
+                },
+                {
+                    "code": "LaterInvolvedNotGuarded",
+                    "message": "subsequently involved role R2 does not subscribe to guard in transition (S0)--[a@R1<A>]-->(S1)",
+                    "transition": {
+                        "source": "S0",
+                        "target": "S1",
+                        "label": { "cmd": "a", "logType": ["A"], "role": "R1" }
+                    },
+                    "roles": ["R2"],
+                    "eventTypes": ["A"],
+                    "fixes": [{ "action": "add", "role": "R2", "eventTypes": ["A"] }]
+                }
+            ])
+        );
+        assert_eq!(
+            errors[1]
+                .fixes
+                .iter()
+                .map(|f| f.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "add event type `A` to role `R2`",
+                "remove event type `A` from role `R1`"
+            ]
+        );
+
+        let fixed = suggest_subscription_fixes(proto.clone(), &subs).unwrap();
+        assert_eq!(check(proto, &fixed).2, vec![]);
+    }
+
+    #[test]
+    fn infer() {
+        setup_logger();
+        let proto = serde_json::from_str::<SwarmProtocol>(
+            r#"{
+                "initial": "S0",
+                "transitions": [
+                    { "source": "S0", "target": "S1", "label": { "cmd": "a", "logType": ["A", "B", "C"], "role": "R1" } },
+                    { "source": "S1", "target": "S2", "label": { "cmd": "b", "logType": ["D", "E"], "role": "R2" } }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let subs = serde_json::from_str::<Subscriptions>(r#"{ "R3": ["C"] }"#).unwrap();
+        let inferred = infer_subscriptions(proto.clone(), &subs).unwrap();
+        assert_eq!(
+            inferred,
+            serde_json::from_str::<Subscriptions>(
+                r#"{
+                    "R1": ["A"],
+                    "R2": ["A", "D"],
+                    "R3": ["C"]
+                }"#
+            )
+            .unwrap()
+        );
+        assert_eq!(check(proto, &inferred).2, vec![]);
+    }
+
+    #[test]
+    fn infer_cycles() {
+        setup_logger();
+        let proto = serde_json::from_str::<SwarmProtocol>(
+            r#"{
+                "initial": "S0",
+                "transitions": [
+                    { "source": "S0", "target": "S1", "label": { "cmd": "C0", "logType": ["R1"], "role": "R1" } },
+                    { "source": "S1", "target": "S2", "label": { "cmd": "C1", "logType": ["R2"], "role": "R2" } },
+                    { "source": "S2", "target": "S1", "label": { "cmd": "C2", "logType": ["R3"], "role": "R3" } },
+                    { "source": "S2", "target": "S3", "label": { "cmd": "C3", "logType": ["R4"], "role": "R4" } },
+                    { "source": "S3", "target": "S2", "label": { "cmd": "C4", "logType": ["R5"], "role": "R5" } },
+                    { "source": "S1", "target": "S4", "label": { "cmd": "C5", "logType": ["R6"], "role": "R6" } }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let inferred = infer_subscriptions(proto.clone(), &BTreeMap::new()).unwrap();
+        assert_eq!(check(proto, &inferred).2, vec![]);
+    }
+
     #[test]
     fn empty_log() {
         setup_logger();
@@ -747,4 +1224,51 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn multiple_initial() {
+        setup_logger();
+        let proto = |initial: &str| {
+            serde_json::from_str::<SwarmProtocol>(&format!(
+                r#"{{
+                    "initial": {initial},
+                    "transitions": [
+                        {{ "source": "Closed", "target": "Opening", "label": {{ "cmd": "open", "logType": ["opening"], "role": "Control" }} }},
+                        {{ "source": "Opening", "target": "Open", "label": {{ "cmd": "finish", "logType": ["opened"], "role": "Door" }} }},
+                        {{ "source": "Open", "target": "Closed", "label": {{ "cmd": "close", "logType": ["closed"], "role": "Control" }} }},
+                        {{ "source": "Locked", "target": "Closed", "label": {{ "cmd": "unlock", "logType": ["unlocked"], "role": "Control" }} }}
+                    ]
+                }}"#
+            ))
+            .unwrap()
+        };
+        let subs = serde_json::from_str::<Subscriptions>(
+            r#"{
+                "Control": ["opening", "opened", "closed", "unlocked"],
+                "Door": ["opening", "opened", "closed", "unlocked"]
+            }"#,
+        )
+        .unwrap();
+        let errors = |initial: &str| {
+            let (g, _, errors) = check(proto(initial), &subs);
+            errors.map(Error::convert(&g))
+        };
+
+        assert_eq!(
+            errors(r#"["Closed", "Open"]"#),
+            vec!["state Locked is unreachable from initial state"]
+        );
+        assert_eq!(errors(r#"["Open", "Locked"]"#), Vec::<String>::new());
+        assert_eq!(
+            errors(r#"["Locked", "Gone"]"#),
+            vec!["initial swarm protocol state has no transitions"]
+        );
+
+        // a single initial state is still written as a plain string
+        let (g, initials, _) = check(proto(r#"["Open", "Locked"]"#), &subs);
+        let json = serde_json::to_value(to_json(&g, &initials.unwrap())).unwrap();
+        assert_eq!(json["initial"], serde_json::json!(["Open", "Locked"]));
+        let json = serde_json::to_value(proto(r#""Locked""#)).unwrap();
+        assert_eq!(json["initial"], serde_json::json!("Locked"));
+    }
 }
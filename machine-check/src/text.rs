@@ -0,0 +1,330 @@
+//! Textual notation for swarm protocols
+//!
+//! A protocol is written as an `initial` declaration followed by one transition per line in the
+//! notation also used in error messages:
+//!
+//! ```text
+//! initial Closed
+//! (Closed) --[open@Control<opening>]--> (Opening)
+//! (Opening) --[open@Door<opened>]--> (Open)
+//! ```
+//!
+//! A protocol with several initial states lists them separated by commas, e.g. `initial Open,
+//! Closed`. States in which the protocol may end are declared likewise, e.g. `final Closed`.
+//! Empty lines and lines starting with `//` are ignored.
+
+use crate::{
+    types::{Command, EventType, Role, State, SwarmLabel, Transition},
+    SwarmProtocol,
+};
+use std::{collections::BTreeMap, fmt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// 1-based line number
+    pub line: usize,
+    /// 1-based column number, counted in characters
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// characters that cannot be part of a name
+const RESERVED: &[char] = &['(', ')', '[', ']', '<', '>', '@', ','];
+
+pub fn parse_swarm(input: &str) -> Result<SwarmProtocol, ParseError> {
+    let mut initial = None;
+    let mut final_states = None;
+    let mut transitions = Vec::new();
+    for (idx, line) in input.lines().enumerate() {
+        let mut p = Parser::new(line, idx + 1);
+        p.skip_ws();
+        if p.at_end() || p.rest().starts_with("//") {
+            continue;
+        }
+        if p.keyword("initial") {
+            let column = p.column();
+            if initial.is_some() {
+                return Err(p.error_at(column, "duplicate initial declaration"));
+            }
+            initial = Some(p.states("initial state")?);
+        } else if p.keyword("final") {
+            let column = p.column();
+            if final_states.is_some() {
+                return Err(p.error_at(column, "duplicate final declaration"));
+            }
+            final_states = Some(p.states("final state")?);
+        } else {
+            transitions.push(p.transition()?);
+        }
+    }
+    let Some(initial) = initial else {
+        let line = input.lines().count().max(1);
+        return Err(ParseError {
+            line,
+            column: 1,
+            message: "missing initial declaration".to_owned(),
+        });
+    };
+    Ok(SwarmProtocol {
+        initial,
+        transitions,
+        final_states: final_states.unwrap_or_default(),
+        schemas: BTreeMap::new(),
+    })
+}
+
+/// Print a swarm protocol in the notation accepted by [`parse_swarm`].
+///
+/// Names containing any of `()[]<>@,` or surrounding whitespace cannot be parsed back, payload
+/// schemas are omitted since the notation has no place for them.
+pub fn print_swarm(proto: &SwarmProtocol) -> String {
+    let mut out = format!("initial {}\n", proto.initial.join(", "));
+    if !proto.final_states.is_empty() {
+        out.push_str(&format!("final {}\n", proto.final_states.join(", ")));
+    }
+    for t in &proto.transitions {
+        out.push_str(&format!(
+            "({}) --[{}]--> ({})\n",
+            t.source, t.label, t.target
+        ));
+    }
+    out
+}
+
+struct Parser<'a> {
+    line: &'a str,
+    /// byte offset into `line`
+    pos: usize,
+    line_no: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(line: &'a str, line_no: usize) -> Self {
+        Self {
+            line,
+            pos: 0,
+            line_no,
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.line[self.pos..]
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos == self.line.len()
+    }
+
+    fn column(&self) -> usize {
+        self.line[..self.pos].chars().count() + 1
+    }
+
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        self.error_at(self.column(), message)
+    }
+
+    fn error_at(&self, column: usize, message: impl Into<String>) -> ParseError {
+        ParseError {
+            line: self.line_no,
+            column,
+            message: message.into(),
+        }
+    }
+
+    fn skip_ws(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), ParseError> {
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{token}`")))
+        }
+    }
+
+    /// consume the keyword if it is followed by whitespace or the end of the line, so that e.g.
+    /// `initialS0` is not taken for a declaration
+    fn keyword(&mut self, word: &str) -> bool {
+        let Some(after) = self.rest().strip_prefix(word) else {
+            return false;
+        };
+        if after.is_empty() || after.starts_with(char::is_whitespace) {
+            self.pos += word.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn end(&mut self) -> Result<(), ParseError> {
+        self.skip_ws();
+        if self.at_end() {
+            Ok(())
+        } else {
+            Err(self.error("unexpected input after end of declaration"))
+        }
+    }
+
+    /// a name is a non-empty sequence of non-reserved characters, surrounding whitespace is dropped
+    fn name(&mut self, what: &str) -> Result<&'a str, ParseError> {
+        self.skip_ws();
+        let rest = self.rest();
+        let len = rest.find(RESERVED).unwrap_or(rest.len());
+        let name = rest[..len].trim_end();
+        if name.is_empty() {
+            return Err(self.error(format!("expected {what}")));
+        }
+        self.pos += len;
+        Ok(name)
+    }
+
+    /// a comma-separated list of states, with or without parentheses, up to the end of the line
+    fn states(&mut self, what: &str) -> Result<Vec<State>, ParseError> {
+        let mut states = Vec::new();
+        loop {
+            self.skip_ws();
+            states.push(if self.rest().starts_with('(') {
+                self.state()?
+            } else {
+                State::new(self.name(what)?)
+            });
+            self.skip_ws();
+            if !self.rest().starts_with(',') {
+                break;
+            }
+            self.expect(",")?;
+        }
+        self.end()?;
+        Ok(states)
+    }
+
+    fn state(&mut self) -> Result<State, ParseError> {
+        self.skip_ws();
+        self.expect("(")?;
+        let name = self.name("state name")?;
+        self.expect(")")?;
+        Ok(State::new(name))
+    }
+
+    fn transition(&mut self) -> Result<Transition<SwarmLabel>, ParseError> {
+        let source = self.state()?;
+        self.skip_ws();
+        self.expect("--[")?;
+        let cmd = Command::new(self.name("command name")?);
+        self.expect("@")?;
+        let role = Role::new(self.name("role name")?);
+        self.expect("<")?;
+        let mut log_type = Vec::new();
+        self.skip_ws();
+        if !self.rest().starts_with('>') {
+            loop {
+                log_type.push(EventType::new(self.name("event type")?));
+                if self.rest().starts_with(',') {
+                    self.pos += 1;
+                } else {
+                    break;
+                }
+            }
+        }
+        self.expect(">")?;
+        self.skip_ws();
+        self.expect("]-->")?;
+        let target = self.state()?;
+        self.end()?;
+        Ok(Transition {
+            label: SwarmLabel {
+                cmd,
+                log_type,
+                role,
+            },
+            source,
+            target,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn round_trip() {
+        let input = r#"
+            // the hangar door
+            initial (Closed)
+            (Closed) --[open@Control<opening>]--> (Opening)
+            (Opening)--[update@Door<opening, progress>]-->(Opening)
+
+            (Opening) --[ finish @ Door < > ]--> (Open Door)
+        "#;
+        let proto = parse_swarm(input).unwrap();
+        let printed = print_swarm(&proto);
+        assert_eq!(
+            printed,
+            "initial Closed\n\
+             (Closed) --[open@Control<opening>]--> (Opening)\n\
+             (Opening) --[update@Door<opening,progress>]--> (Opening)\n\
+             (Opening) --[finish@Door<>]--> (Open Door)\n"
+        );
+        assert_eq!(parse_swarm(&printed).unwrap(), proto);
+
+        let proto = parse_swarm("initial (Open), Closed\nfinal Closed\n").unwrap();
+        assert_eq!(
+            proto.initial,
+            vec![State::new("Open"), State::new("Closed")]
+        );
+        assert_eq!(proto.final_states, vec![State::new("Closed")]);
+        assert_eq!(print_swarm(&proto), "initial Open, Closed\nfinal Closed\n");
+    }
+
+    #[test]
+    fn errors() {
+        let err = |s: &str| parse_swarm(s).unwrap_err().to_string();
+        assert_eq!(
+            err("initial S0\n(S0) --[a@R<A>] (S1)"),
+            "line 2, column 15: expected `]-->`"
+        );
+        assert_eq!(
+            err("initial S0\n(S0) --[@R<A>]--> (S1)"),
+            "line 2, column 9: expected command name"
+        );
+        assert_eq!(
+            err("(S0) --[a@R<A,>]--> (S1)"),
+            "line 1, column 15: expected event type"
+        );
+        assert_eq!(
+            err("(S0) --[a@R<A>]--> (S1)"),
+            "line 1, column 1: missing initial declaration"
+        );
+        assert_eq!(
+            err("initial S0\ninitial S1"),
+            "line 2, column 8: duplicate initial declaration"
+        );
+        assert_eq!(
+            err("initial S0,"),
+            "line 1, column 12: expected initial state"
+        );
+        assert_eq!(
+            err("initial (S0) x"),
+            "line 1, column 14: unexpected input after end of declaration"
+        );
+        assert_eq!(err("initialS0"), "line 1, column 1: expected `(`");
+        assert_eq!(err("initial S0\nfinalS1"), "line 2, column 1: expected `(`");
+    }
+}
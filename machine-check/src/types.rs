@@ -1,6 +1,7 @@
+use crate::Subscriptions;
 use intern_arc::{global::hash_interner, InternedHash};
 use serde::{Deserialize, Serialize};
-use std::{borrow::Borrow, fmt, ops::Deref};
+use std::{borrow::Borrow, collections::BTreeMap, fmt, ops::Deref};
 
 macro_rules! decl_str {
     ($n:ident) => {
@@ -66,13 +67,316 @@ decl_str!(EventType);
 #[serde(tag = "type")]
 pub enum CheckResult {
     OK,
-    ERROR { errors: Vec<String> },
+    ERROR {
+        errors: Vec<String>,
+        diagnostics: Vec<Diagnostic>,
+    },
+}
+
+impl CheckResult {
+    pub fn from_diagnostics(diagnostics: Vec<Diagnostic>) -> Self {
+        if diagnostics.is_empty() {
+            Self::OK
+        } else {
+            let errors = diagnostics.iter().map(|d| d.message.clone()).collect();
+            Self::ERROR {
+                errors,
+                diagnostics,
+            }
+        }
+    }
+}
+
+/// Result of an operation that computes some data, serialised like [`CheckResult`]
+#[derive(Serialize)]
+#[serde(tag = "type")]
+pub enum DataResult<T> {
+    OK {
+        data: T,
+    },
+    ERROR {
+        errors: Vec<String>,
+        diagnostics: Vec<Diagnostic>,
+    },
+}
+
+impl<T> DataResult<T> {
+    pub fn from_result(result: Result<T, Vec<Diagnostic>>) -> Self {
+        match result {
+            Ok(data) => Self::OK { data },
+            Err(diagnostics) => {
+                let errors = diagnostics.iter().map(|d| d.message.clone()).collect();
+                Self::ERROR {
+                    errors,
+                    diagnostics,
+                }
+            }
+        }
+    }
+}
+
+/// Identifies the kind of problem reported by a [`Diagnostic`]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ErrorCode {
+    /// the JSON input could not be parsed
+    ParseError,
+    InitialStateDisconnected,
+    StateUnreachable,
+    LogTypeEmpty,
+    ActiveRoleNotSubscribed,
+    LaterActiveRoleNotSubscribed,
+    LaterInvolvedRoleMoreSubscribed,
+    LaterInvolvedNotGuarded,
+    NonDeterministicGuard,
+    NonDeterministicCommand,
+    GuardNotInvariant,
+    /// the initial state of the machine has no transitions
+    InitialMachineStateDisconnected,
+    /// a command in the machine does not loop back to its source state
+    CommandNotSelfLoop,
+    /// a machine has more than one transition for the same command or event type in a state
+    NonDeterministicTransition,
+    /// the machine lacks a transition present in the projection
+    MissingTransition,
+    /// the machine has a transition not present in the projection
+    ExtraneousTransition,
+    /// the machine does not offer a command of the projection, which makes it less capable but
+    /// not unsafe
+    CommandNotOffered,
+    /// the machine declares a different number of initial states than the projection
+    InitialStateMismatch,
+    /// composed protocols use a shared event type or command with different transitions
+    InterfaceMismatch,
+    /// a replayed log contains an event that is not allowed in the state reached
+    UnexpectedEvent,
+    /// a replayed machine history contains a command that is not enabled in the state reached
+    CommandNotEnabled,
+    /// a conflict simulation could not find competing transitions
+    NoConflict,
+    /// a role’s projection does not follow the transition that won a conflict
+    ConflictUnresolved,
+    /// a role’s projection ends up in a different state than the events accepted by the swarm
+    /// protocol lead to
+    ProjectionDiverged,
+    /// a machine of one protocol revision cannot process the events produced by the other one
+    LogNotConsumable,
+    /// an upgraded machine may execute a command the old protocol revision does not allow
+    UnexpectedCommand,
+    /// a state declared final does not exist in the swarm protocol
+    FinalStateUnknown,
+    /// a reachable state that is not final has no outgoing transitions
+    NonFinalDeadlock,
+    /// no final state can be reached from a reachable state
+    FinalStateUnreachable,
+    /// a role’s projection stops in a state that is not final
+    ProjectionNotFinal,
+    /// composed protocols declare different payload schemas for the same event type
+    SchemaMismatch,
+    /// an event payload does not match the schema of its event type
+    PayloadInvalid,
+    /// a payload schema is malformed or uses keywords the checker does not support
+    SchemaInvalid,
+    /// a cycle of the swarm protocol may repeat forever without the role acting or receiving events
+    RoleStarved,
+}
+
+/// Machine-readable description of a single check error
+///
+/// Only the fields relevant to the given `code` are filled in, the `message` is the same text
+/// that is reported in the `errors` list of [`CheckResult`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    pub code: ErrorCode,
+    pub message: String,
+    /// the state in which the problem occurs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<State>,
+    /// the offending transition
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transition: Option<Transition<DiagnosticLabel>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<Role>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub event_types: Vec<EventType>,
+    /// alternative changes to the subscriptions, each of which fixes this error
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fixes: Vec<SubscriptionFix>,
+    /// path from the initial states of reference and specimen to this error, see
+    /// [`crate::machine::Error::trace`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trace: Vec<TraceStep>,
+    /// index of the offending entry when replaying a log
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<usize>,
+    /// transitions that would have been valid at this point
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub expected: Vec<Transition<DiagnosticLabel>>,
+}
+
+impl Diagnostic {
+    pub fn new(code: ErrorCode, message: String) -> Self {
+        Self {
+            code,
+            message,
+            state: None,
+            transition: None,
+            roles: Vec::new(),
+            event_types: Vec::new(),
+            fixes: Vec::new(),
+            trace: Vec::new(),
+            position: None,
+            expected: Vec::new(),
+        }
+    }
+}
+
+/// One transition taken in both the reference and the specimen machine, together with the
+/// names of the states reached
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TraceStep {
+    pub label: MachineLabel,
+    pub reference: State,
+    pub specimen: State,
+}
+
+/// A change to the subscriptions of a single role
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(tag = "action", rename_all = "camelCase")]
+pub enum SubscriptionFix {
+    #[serde(rename_all = "camelCase")]
+    Add {
+        role: Role,
+        event_types: Vec<EventType>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Remove {
+        role: Role,
+        event_types: Vec<EventType>,
+    },
+}
+
+impl SubscriptionFix {
+    /// apply this fix to the given subscriptions, returns whether they were changed
+    pub fn apply(&self, subs: &mut Subscriptions) -> bool {
+        match self {
+            SubscriptionFix::Add { role, event_types } => {
+                let sub = subs.entry(role.clone()).or_default();
+                let len = sub.len();
+                sub.extend(event_types.iter().cloned());
+                sub.len() != len
+            }
+            SubscriptionFix::Remove { role, event_types } => {
+                let Some(sub) = subs.get_mut(role) else {
+                    return false;
+                };
+                let len = sub.len();
+                for event_type in event_types {
+                    sub.remove(event_type);
+                }
+                sub.len() != len
+            }
+        }
+    }
+}
+
+impl fmt::Display for SubscriptionFix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (action, role, event_types, prep) = match self {
+            SubscriptionFix::Add { role, event_types } => ("add", role, event_types, "to"),
+            SubscriptionFix::Remove { role, event_types } => ("remove", role, event_types, "from"),
+        };
+        let s = if event_types.len() == 1 { "" } else { "s" };
+        write!(f, "{action} event type{s} ")?;
+        for (i, t) in event_types.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "`{t}`")?;
+        }
+        write!(f, " {prep} role `{role}`")
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(untagged)]
+pub enum DiagnosticLabel {
+    Swarm(SwarmLabel),
+    Machine(MachineLabel),
+}
+
+impl fmt::Display for DiagnosticLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiagnosticLabel::Swarm(l) => l.fmt(f),
+            DiagnosticLabel::Machine(l) => l.fmt(f),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Protocol<L> {
-    pub initial: State,
+    /// the states in which the protocol may start, given as a single state name or a list in JSON
+    #[serde(with = "state_list")]
+    pub initial: Vec<State>,
     pub transitions: Vec<Transition<L>>,
+    /// the states in which the protocol may deliberately end, see [`crate::liveness`]
+    #[serde(
+        default,
+        rename = "final",
+        skip_serializing_if = "Vec::is_empty",
+        with = "state_list"
+    )]
+    pub final_states: Vec<State>,
+    /// JSON Schemas for the payloads of the event types, see [`crate::schema`]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub schemas: BTreeMap<EventType, Schema>,
+}
+
+/// JSON Schema for the payload of an event type, see [`crate::schema`]
+///
+/// Schemas are ordered by their JSON text, which is canonical since object keys are kept sorted.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct Schema(pub serde_json::Value);
+
+impl PartialOrd for Schema {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Schema {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.to_string().cmp(&other.0.to_string())
+    }
+}
+
+/// a single state name or a list of them
+mod state_list {
+    use super::State;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum States {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    pub fn serialize<S: Serializer>(states: &[State], serializer: S) -> Result<S::Ok, S::Error> {
+        match states {
+            [state] => state.serialize(serializer),
+            states => states.serialize(serializer),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<State>, D::Error> {
+        Ok(match States::deserialize(deserializer)? {
+            States::One(state) => vec![State::new(&state)],
+            States::Many(states) => states.iter().map(|s| State::new(s)).collect(),
+        })
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -115,7 +419,7 @@ impl fmt::Display for MachineLabel {
         match self {
             MachineLabel::Execute { cmd, log_type } => {
                 write!(f, "{}/", cmd)?;
-                print_log(&log_type, f)
+                print_log(log_type, f)
             }
             MachineLabel::Input { event_type } => write!(f, "{event_type}?"),
         }
@@ -0,0 +1,443 @@
+//! JSON string entry points used by the npm package, see `src/index.ts`
+
+use crate::{
+    composition, export, monitor, simulation, swarm, text,
+    types::{CheckResult, DataResult, Diagnostic, ErrorCode, EventType, Role},
+    Machine, MapVec, Subscriptions, SwarmProtocol,
+};
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+pub fn check_swarm(proto: String, subs: String) -> String {
+    let proto = match serde_json::from_str::<SwarmProtocol>(&proto) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing swarm protocol: {}", e)),
+    };
+    let subs = match serde_json::from_str::<Subscriptions>(&subs) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing subscriptions: {}", e)),
+    };
+    let (graph, _, errors) = swarm::check(proto, &subs);
+    result(errors.map(swarm::Error::diagnostic(&graph)))
+}
+
+#[wasm_bindgen]
+pub fn infer_subscriptions(proto: String, subs: String) -> String {
+    let proto = match serde_json::from_str::<SwarmProtocol>(&proto) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing swarm protocol: {}", e)),
+    };
+    let subs = match serde_json::from_str::<Subscriptions>(&subs) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing subscriptions: {}", e)),
+    };
+    // inference only fails if the graph cannot be prepared, which `from_json` reports
+    let result = swarm::infer_subscriptions(proto.clone(), &subs)
+        .map_err(|_| swarm::from_json(proto, &subs).2);
+    serde_json::to_string(&DataResult::from_result(result)).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn suggest_subscription_fixes(proto: String, subs: String) -> String {
+    let proto = match serde_json::from_str::<SwarmProtocol>(&proto) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing swarm protocol: {}", e)),
+    };
+    let subs = match serde_json::from_str::<Subscriptions>(&subs) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing subscriptions: {}", e)),
+    };
+    let result = swarm::suggest_subscription_fixes(proto.clone(), &subs).map_err(|errors| {
+        // the graph only depends on the protocol, so the edge and node IDs match
+        let (graph, _, _) = swarm::check(proto, &subs);
+        errors.map(swarm::Error::diagnostic(&graph))
+    });
+    serde_json::to_string(&DataResult::from_result(result)).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn project_machine(swarm: String, subs: String, role: String) -> String {
+    let swarm = match serde_json::from_str::<SwarmProtocol>(&swarm) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing swarm protocol: {}", e)),
+    };
+    let subs = match serde_json::from_str::<Subscriptions>(&subs) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing subscriptions: {}", e)),
+    };
+    let role = Role::new(&role);
+    let result = crate::project_machine(&swarm, &subs, role);
+    serde_json::to_string(&DataResult::from_result(result)).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn swarm_dot(proto: String, subs: String) -> String {
+    let proto = match serde_json::from_str::<SwarmProtocol>(&proto) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing swarm protocol: {}", e)),
+    };
+    let subs = match serde_json::from_str::<Subscriptions>(&subs) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing subscriptions: {}", e)),
+    };
+    let result = Ok(crate::swarm_dot(&proto, &subs));
+    serde_json::to_string(&DataResult::from_result(result)).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn projection_dot(swarm: String, subs: String, role: String) -> String {
+    let swarm = match serde_json::from_str::<SwarmProtocol>(&swarm) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing swarm protocol: {}", e)),
+    };
+    let subs = match serde_json::from_str::<Subscriptions>(&subs) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing subscriptions: {}", e)),
+    };
+    let role = Role::new(&role);
+    let result = crate::projection_dot(&swarm, &subs, role);
+    serde_json::to_string(&DataResult::from_result(result)).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn swarm_mermaid(proto: String) -> String {
+    let proto = match serde_json::from_str::<SwarmProtocol>(&proto) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing swarm protocol: {}", e)),
+    };
+    serde_json::to_string(&DataResult::from_result(Ok(export::swarm_to_mermaid(
+        &proto,
+    ))))
+    .unwrap()
+}
+
+#[wasm_bindgen]
+pub fn machine_mermaid(machine: String) -> String {
+    let machine = match serde_json::from_str::<Machine>(&machine) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing machine: {}", e)),
+    };
+    serde_json::to_string(&DataResult::from_result(Ok(export::machine_to_mermaid(
+        &machine,
+    ))))
+    .unwrap()
+}
+
+#[wasm_bindgen]
+pub fn swarm_plantuml(proto: String) -> String {
+    let proto = match serde_json::from_str::<SwarmProtocol>(&proto) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing swarm protocol: {}", e)),
+    };
+    serde_json::to_string(&DataResult::from_result(Ok(export::swarm_to_plantuml(
+        &proto,
+    ))))
+    .unwrap()
+}
+
+#[wasm_bindgen]
+pub fn machine_plantuml(machine: String) -> String {
+    let machine = match serde_json::from_str::<Machine>(&machine) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing machine: {}", e)),
+    };
+    serde_json::to_string(&DataResult::from_result(Ok(export::machine_to_plantuml(
+        &machine,
+    ))))
+    .unwrap()
+}
+
+#[wasm_bindgen]
+pub fn parse_swarm(input: String) -> String {
+    match text::parse_swarm(&input) {
+        Ok(proto) => serde_json::to_string(&DataResult::from_result(Ok(proto))).unwrap(),
+        Err(e) => parse_err(format!("parsing swarm protocol: {}", e)),
+    }
+}
+
+#[wasm_bindgen]
+pub fn print_swarm(proto: String) -> String {
+    let proto = match serde_json::from_str::<SwarmProtocol>(&proto) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing swarm protocol: {}", e)),
+    };
+    serde_json::to_string(&DataResult::from_result(Ok(text::print_swarm(&proto)))).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn check_projection(swarm: String, subs: String, role: String, machine: String) -> String {
+    let swarm = match serde_json::from_str::<SwarmProtocol>(&swarm) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing swarm protocol: {}", e)),
+    };
+    let subs = match serde_json::from_str::<Subscriptions>(&subs) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing subscriptions: {}", e)),
+    };
+    let role = Role::new(&role);
+    let machine = match serde_json::from_str::<Machine>(&machine) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing machine: {}", e)),
+    };
+
+    let result = crate::check_projection(&swarm, &subs, role, &machine);
+    serde_json::to_string(&DataResult::from_result(result)).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn check_refinement(swarm: String, subs: String, role: String, machine: String) -> String {
+    let swarm = match serde_json::from_str::<SwarmProtocol>(&swarm) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing swarm protocol: {}", e)),
+    };
+    let subs = match serde_json::from_str::<Subscriptions>(&subs) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing subscriptions: {}", e)),
+    };
+    let role = Role::new(&role);
+    let machine = match serde_json::from_str::<Machine>(&machine) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing machine: {}", e)),
+    };
+
+    let result = crate::check_refinement(&swarm, &subs, role, &machine);
+    serde_json::to_string(&DataResult::from_result(result)).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn check_compatibility(old: String, new: String, subs_old: String, subs_new: String) -> String {
+    let old = match serde_json::from_str::<SwarmProtocol>(&old) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing old swarm protocol: {}", e)),
+    };
+    let new = match serde_json::from_str::<SwarmProtocol>(&new) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing new swarm protocol: {}", e)),
+    };
+    let subs_old = match serde_json::from_str::<Subscriptions>(&subs_old) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing old subscriptions: {}", e)),
+    };
+    let subs_new = match serde_json::from_str::<Subscriptions>(&subs_new) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing new subscriptions: {}", e)),
+    };
+    let result = crate::check_compatibility(&old, &new, &subs_old, &subs_new);
+    serde_json::to_string(&DataResult::from_result(result)).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn check_liveness(swarm: String, subs: String) -> String {
+    let swarm = match serde_json::from_str::<SwarmProtocol>(&swarm) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing swarm protocol: {}", e)),
+    };
+    let subs = match serde_json::from_str::<Subscriptions>(&subs) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing subscriptions: {}", e)),
+    };
+    result(
+        crate::check_liveness(&swarm, &subs)
+            .err()
+            .unwrap_or_default(),
+    )
+}
+
+#[wasm_bindgen]
+pub fn check_starvation(swarm: String, subs: String) -> String {
+    let swarm = match serde_json::from_str::<SwarmProtocol>(&swarm) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing swarm protocol: {}", e)),
+    };
+    let subs = match serde_json::from_str::<Subscriptions>(&subs) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing subscriptions: {}", e)),
+    };
+    result(
+        crate::check_starvation(&swarm, &subs)
+            .err()
+            .unwrap_or_default(),
+    )
+}
+
+#[wasm_bindgen]
+pub fn minimise_swarm(proto: String) -> String {
+    let proto = match serde_json::from_str::<SwarmProtocol>(&proto) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing swarm protocol: {}", e)),
+    };
+    let result = crate::minimise_swarm(&proto);
+    serde_json::to_string(&DataResult::from_result(result)).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn minimise_machine(machine: String) -> String {
+    let machine = match serde_json::from_str::<Machine>(&machine) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing machine: {}", e)),
+    };
+    let result = crate::minimise_machine(&machine);
+    serde_json::to_string(&DataResult::from_result(result)).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn compose_protocols(protos: String) -> String {
+    let protos = match serde_json::from_str::<Vec<SwarmProtocol>>(&protos) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing swarm protocols: {}", e)),
+    };
+    match composition::compose(&protos) {
+        Some(proto) => serde_json::to_string(&DataResult::from_result(Ok(proto))).unwrap(),
+        None => parse_err("no swarm protocols to compose".to_owned()),
+    }
+}
+
+#[wasm_bindgen]
+pub fn check_composition(protos: String, subs: String) -> String {
+    let protos = match serde_json::from_str::<Vec<SwarmProtocol>>(&protos) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing swarm protocols: {}", e)),
+    };
+    let subs = match serde_json::from_str::<Vec<Subscriptions>>(&subs) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing subscriptions: {}", e)),
+    };
+    result(
+        crate::check_composition(&protos, &subs)
+            .err()
+            .unwrap_or_default(),
+    )
+}
+
+#[wasm_bindgen]
+pub fn check_composition_interfaces(protos: String, subs: String) -> String {
+    let protos = match serde_json::from_str::<Vec<SwarmProtocol>>(&protos) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing swarm protocols: {}", e)),
+    };
+    let subs = match serde_json::from_str::<Vec<Subscriptions>>(&subs) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing subscriptions: {}", e)),
+    };
+    result(
+        crate::check_composition_interfaces(&protos, &subs)
+            .err()
+            .unwrap_or_default(),
+    )
+}
+
+#[wasm_bindgen]
+pub fn replay_log(swarm: String, subs: String, log: String) -> String {
+    let swarm = match serde_json::from_str::<SwarmProtocol>(&swarm) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing swarm protocol: {}", e)),
+    };
+    let subs = match serde_json::from_str::<Subscriptions>(&subs) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing subscriptions: {}", e)),
+    };
+    let log = match serde_json::from_str::<Vec<(EventType, Role)>>(&log) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing log: {}", e)),
+    };
+    let result = crate::replay_log(&swarm, &subs, &log);
+    serde_json::to_string(&DataResult::from_result(result)).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn validate_payloads(swarm: String, log: String) -> String {
+    let swarm = match serde_json::from_str::<SwarmProtocol>(&swarm) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing swarm protocol: {}", e)),
+    };
+    let log = match serde_json::from_str::<Vec<(EventType, serde_json::Value)>>(&log) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing log: {}", e)),
+    };
+    result(
+        crate::validate_payloads(&swarm, &log)
+            .err()
+            .unwrap_or_default(),
+    )
+}
+
+#[wasm_bindgen]
+pub fn replay_machine(swarm: String, subs: String, role: String, history: String) -> String {
+    let swarm = match serde_json::from_str::<SwarmProtocol>(&swarm) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing swarm protocol: {}", e)),
+    };
+    let subs = match serde_json::from_str::<Subscriptions>(&subs) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing subscriptions: {}", e)),
+    };
+    let role = Role::new(&role);
+    let history = match serde_json::from_str::<Vec<monitor::Observation>>(&history) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing machine history: {}", e)),
+    };
+    let result = crate::replay_machine(&swarm, &subs, role, &history);
+    serde_json::to_string(&DataResult::from_result(result)).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn simulate(swarm: String, subs: String, options: String) -> String {
+    let swarm = match serde_json::from_str::<SwarmProtocol>(&swarm) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing swarm protocol: {}", e)),
+    };
+    let subs = match serde_json::from_str::<Subscriptions>(&subs) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing subscriptions: {}", e)),
+    };
+    let options = match serde_json::from_str::<simulation::SimulationOptions>(&options) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing simulation options: {}", e)),
+    };
+    let result = crate::simulate(&swarm, &subs, &options);
+    serde_json::to_string(&DataResult::from_result(result)).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn simulate_conflict(swarm: String, subs: String, options: String) -> String {
+    let swarm = match serde_json::from_str::<SwarmProtocol>(&swarm) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing swarm protocol: {}", e)),
+    };
+    let subs = match serde_json::from_str::<Subscriptions>(&subs) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing subscriptions: {}", e)),
+    };
+    let options = match serde_json::from_str::<simulation::ConflictOptions>(&options) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing conflict options: {}", e)),
+    };
+    let result = crate::simulate_conflict(&swarm, &subs, &options);
+    serde_json::to_string(&DataResult::from_result(result)).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn explore(swarm: String, subs: String, options: String) -> String {
+    let swarm = match serde_json::from_str::<SwarmProtocol>(&swarm) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing swarm protocol: {}", e)),
+    };
+    let subs = match serde_json::from_str::<Subscriptions>(&subs) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing subscriptions: {}", e)),
+    };
+    let options = match serde_json::from_str::<crate::explore::ExploreOptions>(&options) {
+        Ok(p) => p,
+        Err(e) => return parse_err(format!("parsing explore options: {}", e)),
+    };
+    let result = crate::explore(&swarm, &subs, &options);
+    serde_json::to_string(&DataResult::from_result(result)).unwrap()
+}
+
+fn result(diagnostics: Vec<Diagnostic>) -> String {
+    serde_json::to_string(&CheckResult::from_diagnostics(diagnostics)).unwrap()
+}
+
+fn parse_err(message: String) -> String {
+    result(vec![Diagnostic::new(ErrorCode::ParseError, message)])
+}